@@ -61,8 +61,8 @@ fn parse_tick_duration(value: &str) -> Result<Duration, String> {
 
 pub struct Decoder {
     counter: CounterMeta,
-    tx: SenderTx<Vec<DataPair>>,
-    thread_handle: thread::JoinHandle<()>,
+    tx: BoundedSenderTx<Vec<DataPair>>,
+    stage: StageHandle,
 }
 
 #[derive(Clone, Debug)]
@@ -75,12 +75,21 @@ pub struct ObjectStats {
 pub struct CounterStats {
     pub meta: CounterMeta,
     pub decoded: String,
+    pub decode_errors: usize,
     pub signal: VecDeque<bool>,
     pub instances: Vec<InstanceStats>,
 }
 
 const HIST_SIZE: usize = 200;
 
+/// How many ticks worth of samples may queue up for a decoder thread.
+const DECODER_BACKLOG: usize = 64;
+
+/// Name of the instrumented decoder stage of the counter in the global `MetricsRegistry`.
+pub fn stage_name(counter: &CounterMeta, stage: &str) -> String {
+    format!("{} {}", counter.name_index, stage)
+}
+
 #[derive(Clone, Debug)]
 pub struct InstanceStats {
     pub instance_id: InstanceId,
@@ -118,6 +127,7 @@ impl CounterStats {
         CounterStats {
             meta,
             decoded: String::with_capacity(HIST_SIZE),
+            decode_errors: 0,
             signal: VecDeque::with_capacity(HIST_SIZE),
             instances: vec![],
         }
@@ -306,7 +316,7 @@ impl AppInner {
         &mut self,
         counter: &CounterMeta,
         values: Vec<DataPair>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), FlowError> {
         let decoder = self.decoder_for(counter);
         decoder.tx.send(values)
     }
@@ -323,15 +333,20 @@ impl AppInner {
     }
 
     fn spawn_decoder(&mut self, counter: &CounterMeta) -> &mut Decoder {
-        let (tx, rx) = signal_flow::pair::pair();
+        // Decoder must not hold UI thread back. RTSM tolerates lost samples anyway.
+        let (tx, rx) = signal_flow::pair::bounded(DECODER_BACKLOG, OverflowPolicy::DropOldest);
         let stats = Arc::clone(self.stats());
         let counter_clone = counter.clone();
+        let mut rx = Some(rx);
 
-        let thread_handle = std::thread::spawn(move || {
-            let counter = counter_clone;
-            let ranges = RtsmRanges::new(10..50, 60..100).unwrap();
+        let stage = spawn_supervised(RestartPolicy::Never, move |_| {
+            let counter = &counter_clone;
+            let plan = crate::reg::get_range_plan();
 
             let mut decoder = rx
+                .take()
+                .expect("decoder is never restarted")
+                .instrument(&stage_name(&counter, "samples"))
                 .map(|mut vec: Vec<DataPair>| {
                     let mut lock = stats.write().unwrap();
                     let counter = lock.counter_mut(&counter);
@@ -343,7 +358,9 @@ impl AppInner {
                     vec.sort_by(|left, right| left.0.cmp(&right.0));
                     vec.into_iter().map(|pair| pair.1)
                 })
-                .rtsm_multi(|_| ranges.clone())
+                .rtsm_multi(|i| plan.lane_ranges(i))
+                // provider updates instances one by one, and may be sampled in between
+                .with_skew_window(2)
                 .flatten()
                 .map(|signal| {
                     let mut lock = stats.write().unwrap();
@@ -352,24 +369,27 @@ impl AppInner {
                     signal
                 })
                 .morse_decode::<ITU>()
+                .errors_to(CustomTx::new(|_: FlowError| {
+                    let mut lock = stats.write().unwrap();
+                    let counter = lock.counter_mut(&counter);
+                    counter.decode_errors += 1;
+                    Ok(())
+                }))
+                .instrument(&stage_name(&counter, "chars"))
                 .map(|char| {
                     let mut lock = stats.write().unwrap();
                     let counter = lock.counter_mut(&counter);
                     counter.push_char(char);
                     char
                 });
-            loop {
-                match decoder.recv() {
-                    Ok(None) => break,
-                    Ok(Some(_)) => {}
-                    Err(_) => { /*try recover*/ }
-                }
-            }
+            // errors are reported to stats, so it only stops when the pair hangs up.
+            while let Ok(Some(_)) = decoder.recv() {}
+            Ok(())
         });
         let decoder = Decoder {
             counter: counter.clone(),
             tx,
-            thread_handle,
+            stage,
         };
         self.decoders.push(decoder);
         self.decoders.last_mut().unwrap()
@@ -379,12 +399,10 @@ impl AppInner {
 impl Drop for AppInner {
     fn drop(&mut self) {
         for d in self.decoders.drain(..) {
-            let Decoder {
-                tx, thread_handle, ..
-            } = d;
+            let Decoder { tx, stage, .. } = d;
             drop(tx);
-            if let Err(e) = thread_handle.join() {
-                println!("Thread panic: {:?}", e);
+            if let Err(e) = stage.join() {
+                println!("Decoder failed: {}", e);
             }
         }
     }
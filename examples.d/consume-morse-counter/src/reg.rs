@@ -1,10 +1,14 @@
 use std::time::Duration;
 
+use signal_flow::rtsm::RangePlan;
 use win_high::perf::consume::*;
 use win_high::prelude::v2::*;
 
 const SUB_KEY_MORSE: &str = r"SYSTEM\CurrentControlSet\Services\Morse";
 const VALUE_NAME_TICK_INTERVAL: &str = "TickIntervalMillis";
+const VALUE_NAME_RANGE_PLAN: &str = "RangePlan";
+/// Same as provider's.
+const DEFAULT_RANGE_PLAN: &str = "10..90/4x2";
 
 /// Similar to provider, but divides duration by 2.
 pub fn get_tick_interval() -> Duration {
@@ -21,6 +25,22 @@ pub fn get_tick_interval() -> Duration {
     Duration::from_millis(1250 / 2)
 }
 
+/// Same as provider.
+pub fn get_range_plan() -> RangePlan<u32> {
+    let sub_key = U16CString::from_str(SUB_KEY_MORSE).unwrap();
+    if let Ok(hkey) =
+        RegOpenKeyEx_Safe(HKEY_LOCAL_MACHINE, PCWSTR(sub_key.as_ptr()), None, KEY_READ)
+    {
+        if let Ok(buffer) = query_value(*hkey, VALUE_NAME_RANGE_PLAN, None, None) {
+            let string = unsafe { U16CStr::from_ptr_str(buffer.as_ptr() as *const _) };
+            if let Ok(plan) = string.to_string_lossy().parse() {
+                return plan;
+            }
+        }
+    }
+    DEFAULT_RANGE_PLAN.parse().unwrap()
+}
+
 pub fn get_object_name_index() -> u32 {
     let all = get_counters_info(None, UseLocale::English).unwrap();
     all.map()
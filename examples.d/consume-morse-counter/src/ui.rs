@@ -11,7 +11,9 @@ use ratatui::{
     widgets::{Axis, Block, Borders, Chart, Dataset, Paragraph, Tabs, Wrap},
 };
 
-use crate::{App, CounterStats};
+use signal_flow::MetricsRegistry;
+
+use crate::{App, CounterStats, stage_name};
 
 // colors
 const COLOR_PRIMARY: Color = Color::Cyan;
@@ -98,6 +100,20 @@ fn draw_tabs(f: &mut Frame<'_>, app: &App, area: Rect) -> io::Result<Rect> {
     Ok(chunks[1])
 }
 
+/// Rate of samples coming into decoder, and how long ago the last character was decoded.
+fn pretty_throughput(stat: &CounterStats) -> String {
+    let registry = MetricsRegistry::global();
+    let samples = registry.stage(&stage_name(&stat.meta, "samples"));
+    let chars = registry.stage(&stage_name(&stat.meta, "chars"));
+    let rate = samples.and_then(|stage| stage.stats.throughput());
+    let idle = chars.and_then(|stage| stage.idle);
+    format!(
+        "{:.1} samples/s, last char {:.1}s ago",
+        rate.unwrap_or_default(),
+        idle.unwrap_or_default().as_secs_f64()
+    )
+}
+
 fn draw_stat(f: &mut Frame<'_>, app: &App, stat: CounterStats, area: Rect) -> io::Result<()> {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -125,7 +141,11 @@ fn draw_stat(f: &mut Frame<'_>, app: &App, stat: CounterStats, area: Rect) -> io
         .alignment(Alignment::Right)
         .block(
             Block::default()
-                .title("Decoded signal")
+                .title(format!(
+                    "Decoded signal ({} errors, {})",
+                    stat.decode_errors,
+                    pretty_throughput(&stat)
+                ))
                 .title_style(Style::default().fg(COLOR_PRIMARY))
                 .borders(Borders::TOP),
         )
@@ -1,8 +1,4 @@
-use std::error::Error;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-
-use log::error;
+use log::{error, info};
 
 use morse_stream::*;
 use signal_flow::rtsm::*;
@@ -14,12 +10,11 @@ use win_high::prelude::v2::*;
 use crate::reg::*;
 use crate::strings_providers::{ConstString, RandomJokeProvider, StringsProvider};
 use crate::symbols;
-use crate::worker::WorkerThread;
 
 /// App manages counters, instances, synchronization etc.
 pub struct App {
     running: bool,
-    workers: Vec<WorkerThread<()>>,
+    workers: Vec<StageHandle>,
     shared_data: SharedObjectData,
     counters: Vec<CounterId>,
     instances: Vec<InstanceId>,
@@ -113,9 +108,26 @@ impl App {
         }
         for worker in self.workers.drain(..) {
             if let Err(e) = worker.join() {
-                error!("Error while stopping global worker: {:?}", e);
+                error!("Error while stopping global worker: {}", e);
             }
         }
+        log_metrics();
+    }
+}
+
+/// Report throughput and blocking of every worker stage.
+fn log_metrics() {
+    for stage in MetricsRegistry::global().snapshot() {
+        let stats = stage.stats;
+        info!(
+            "{}: {} items, {} errors, {:.2} items/s, {:?} blocked on average, idle for {:?}",
+            stage.name,
+            stats.items,
+            stats.errors,
+            stats.throughput().unwrap_or_default(),
+            stats.mean_blocking().unwrap_or_default(),
+            stage.idle.unwrap_or_default(),
+        );
     }
 }
 
@@ -144,50 +156,34 @@ impl WorkerBuilder {
         }
     }
 
-    pub fn build(self) -> WorkerThread<()> {
-        WorkerThread::spawn(move |cancellation_token: Arc<AtomicBool>| {
+    pub fn build(mut self) -> StageHandle {
+        // a panicking counter pipeline starts over, rather than freezing the counter for good
+        let policy = RestartPolicy::Restart {
+            max_restarts: 3,
+            delay: get_tick_interval(),
+        };
+        spawn_supervised(policy, move |cancellation_token| {
             self.main(cancellation_token)
         })
     }
 
-    fn main(self, cancellation_token: Arc<AtomicBool>) {
+    fn main(&mut self, cancellation_token: &CancellationToken) -> Result<(), FlowError> {
         let WorkerBuilder {
             shared_data,
             counter,
             instances,
-            num_instances,
-            mut strings_provider,
+            strings_provider,
+            ..
         } = self;
+        let counter = *counter;
+        let plan = get_range_plan();
 
-        let mut rtsm_coders: Vec<(RtsmTx<_>, signal_flow::pair::ReceiverRx<_>)> = Vec::new();
-        for (i, _instance) in instances.iter().cloned().enumerate() {
-            let (tx, rx) = signal_flow::pair::pair();
-            // let ranges = RtsmRanges::new(10..50, 60..100).unwrap();
-            let off = 10 + 10 * (i as u32 % 4);
-            let on = 60 + 10 * (i as u32 % 4);
-            let ranges = RtsmRanges::new(off..off + 10, on..on + 10).unwrap();
-            let rtsm = RtsmTx::new(ranges, tx);
-            rtsm_coders.push((rtsm, rx));
-        }
-
-        let mut tx = CustomTx::new(|signals: Vec<bool>| -> Result<(), Box<dyn Error>> {
-            assert_eq!(signals.len(), rtsm_coders.len());
-            assert_ne!(instances.len(), 0);
+        let mut tx = CustomTx::new(|values: Vec<u32>| -> Result<(), FlowError> {
+            assert_eq!(values.len(), instances.len());
 
             shared_data.update(|mut data| {
-                for i in 0..signals.len() {
-                    let signal = signals[i];
-                    let instance = &instances[i];
-                    let (coder, rx) = &mut rtsm_coders[i];
-
-                    coder.send(signal).unwrap();
-                    let value = rx
-                        .recv()
-                        .unwrap()
-                        .ok_or("Unexpected None from one-to-one rx/tx pair")
-                        .unwrap();
-
-                    let counter_value = CounterValue::Dword(value);
+                for (instance, value) in instances.iter().zip(values.iter()) {
+                    let counter_value = CounterValue::Dword(*value);
                     data.set(counter, instance.clone(), counter_value);
                 }
                 data
@@ -195,22 +191,34 @@ impl WorkerBuilder {
 
             Ok(())
         })
-        .cancel_on(cancellation_token)
+        .rtsm_multi(|i| plan.lane_ranges(i))
+        .instrument(&format!("{:?} counters", counter))
         .interval(get_tick_interval())
+        // don't make `StageHandle::join` wait for the next tick
+        .with_cancellation(cancellation_token.clone())
+        // blocking of this stage includes waiting for ticks
+        .instrument(&format!("{:?} ticks", counter))
         .chunks(instances.len())
-        .morse_encode::<ITU>();
-
-        'outer: loop {
+        // every instance must get a signal on each tick
+        .with_partial_policy(PartialChunkPolicy::Pad(|| morse_stream::OFF))
+        .morse_encode::<ITU>()
+        .instrument(&format!("{:?} chars", counter))
+        // line breaks of multi-line jokes would otherwise be encoded as unknown characters
+        .filter(|char: &char| !char.is_control())
+        // Cancel between characters rather than mid-letter, so that closing the pipeline on
+        // shutdown leaves all instances in a clean pause.
+        .cancel_on(cancellation_token.clone());
+
+        let sent = 'outer: loop {
             let string = strings_provider.provide();
             for char in string.chars().chain(" ".chars()) {
-                match tx.send(char) {
-                    Err(e) => {
-                        error!("Worker error : {}", e);
-                        break 'outer;
-                    }
-                    _ => {}
+                if let Err(e) = tx.send(char) {
+                    break 'outer Err(e);
                 }
             }
-        }
+        };
+
+        let closed = tx.close();
+        sent.and(closed)
     }
 }
@@ -18,7 +18,6 @@ mod app;
 mod morse;
 mod reg;
 mod strings_providers;
-mod worker;
 
 #[allow(dead_code)]
 mod symbols {
@@ -1,6 +1,7 @@
 use std::convert::TryFrom;
 use std::time::Duration;
 
+use signal_flow::rtsm::RangePlan;
 use win_high::perf::useful::*;
 use win_high::prelude::v2::*;
 
@@ -10,6 +11,9 @@ const SUB_KEY_MORSE: &str = r"SYSTEM\CurrentControlSet\Services\Morse";
 const VALUE_NAME_CUSTOM_MESSAGE: &str = "CustomMessage";
 const VALUE_NAME_NUM_INSTANCES: &str = "NumInstances";
 const VALUE_NAME_TICK_INTERVAL: &str = "TickIntervalMillis";
+const VALUE_NAME_RANGE_PLAN: &str = "RangePlan";
+/// Consumer falls back to the same plan.
+const DEFAULT_RANGE_PLAN: &str = "10..90/4x2";
 
 pub fn get_number_of_instances() -> NumInstances {
     let sub_key = U16CString::from_str(SUB_KEY_MORSE).unwrap();
@@ -43,6 +47,22 @@ pub fn get_tick_interval() -> Duration {
     Duration::from_millis(1250)
 }
 
+/// How instances share values of a counter.
+pub fn get_range_plan() -> RangePlan<u32> {
+    let sub_key = U16CString::from_str(SUB_KEY_MORSE).unwrap();
+    if let Ok(hkey) =
+        RegOpenKeyEx_Safe(HKEY_LOCAL_MACHINE, PCWSTR(sub_key.as_ptr()), None, KEY_READ)
+    {
+        if let Ok(buffer) = query_value(*hkey, VALUE_NAME_RANGE_PLAN, None, None) {
+            let string = unsafe { U16CStr::from_ptr_str(buffer.as_ptr() as *const _) };
+            if let Ok(plan) = string.to_string_lossy().parse() {
+                return plan;
+            }
+        }
+    }
+    DEFAULT_RANGE_PLAN.parse().unwrap()
+}
+
 pub fn get_reg_key_strings_provider() -> RegKeyStringsProvider {
     RegKeyStringsProvider::new(SUB_KEY_MORSE, VALUE_NAME_CUSTOM_MESSAGE)
 }
@@ -0,0 +1,133 @@
+//! Asynchronous twins of `EncoderTx` and `DecoderRx`.
+//!
+//! Both wrap their blocking counterparts, which do all the Morse-specific work synchronously
+//! and only touch the async inner `Tx`/`Rx` at the edges.
+use signal_flow::*;
+
+use super::*;
+
+pub struct AsyncEncoderTx<D, X> {
+    /// Blocking encoder which collects signal of the current character.
+    encoder: EncoderTx<D, Vec<Signal>>,
+    tx: X,
+}
+
+impl<D: Dialect, X: AsyncTx<Item = Signal>> AsyncEncoderTx<D, X> {
+    pub fn new(tx: X) -> Self {
+        AsyncEncoderTx {
+            encoder: EncoderTx::new(Vec::new()),
+            tx,
+        }
+    }
+
+    /// Send signal collected by the blocking encoder so far.
+    async fn send_collected(&mut self) -> Result<(), FlowError> {
+        for signal in self.encoder.tx.drain(..) {
+            self.tx.send(signal).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush_collected(&mut self) -> Result<(), FlowError> {
+        self.encoder.flush()?;
+        self.send_collected().await
+    }
+}
+
+impl<D: Dialect, X: AsyncTx<Item = Signal>> AsyncTx for AsyncEncoderTx<D, X> {
+    type Item = char;
+
+    async fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.encoder.send_char(value)?;
+        self.send_collected().await
+    }
+
+    async fn flush(&mut self) -> Result<(), FlowError> {
+        self.flush_collected().await?;
+        self.tx.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), FlowError> {
+        let flushed = self.flush_collected().await;
+        let closed = self.tx.close().await;
+        flushed.and(closed)
+    }
+}
+
+pub struct AsyncDecoderRx<D, X> {
+    /// Blocking decoder which is fed with signal units manually.
+    decoder: DecoderRx<D, ()>,
+    inner: X,
+}
+
+impl<D: Dialect, X: AsyncRx<Item = Signal>> AsyncDecoderRx<D, X> {
+    pub fn new(inner: X) -> Self {
+        AsyncDecoderRx {
+            decoder: DecoderRx::new(()),
+            inner,
+        }
+    }
+}
+
+impl<D: Dialect, X: AsyncRx<Item = Signal>> AsyncRx for AsyncDecoderRx<D, X> {
+    type Item = char;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        loop {
+            let signal = self.inner.recv().await?;
+            if let Some(char) = self.decoder.process_signal_unit(signal)? {
+                return Ok(char);
+            }
+        }
+    }
+}
+
+pub trait AsyncMorseTxExt: AsyncTx<Item = Signal> {
+    fn morse_encode<D: Dialect>(self) -> AsyncEncoderTx<D, Self>
+    where
+        Self: Sized,
+    {
+        AsyncEncoderTx::new(self)
+    }
+}
+
+impl<X: AsyncTx<Item = Signal>> AsyncMorseTxExt for X {}
+
+pub trait AsyncMorseRxExt: AsyncRx<Item = Signal> {
+    fn morse_decode<D: Dialect>(self) -> AsyncDecoderRx<D, Self>
+    where
+        Self: Sized,
+    {
+        AsyncDecoderRx::new(self)
+    }
+}
+
+impl<X: AsyncRx<Item = Signal>> AsyncMorseRxExt for X {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use signal_flow::rtsm::*;
+
+    #[test]
+    fn test_morse_over_rtsm() {
+        block_on(async {
+            let ranges = RtsmRanges::new(10..40, 60..90).unwrap();
+            let (sender, receiver) = mpsc::unbounded();
+
+            let mut tx = SinkTx::new(sender)
+                .rtsm(ranges.clone())
+                .morse_encode::<ITU>();
+            for char in "SOS".chars() {
+                tx.send(char).await.unwrap();
+            }
+            tx.close().await.unwrap();
+
+            let rx = StreamRx::new(receiver).rtsm(ranges).morse_decode::<ITU>();
+            let decoded: String = rx.collect_vec().await.unwrap().into_iter().collect();
+            assert_eq!(decoded, "SOS");
+        });
+    }
+}
@@ -2,15 +2,27 @@
 //!
 //! Supports International (ITU) dialect.
 #![deny(dead_code)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::error::Error;
-use std::fmt::{self, Debug};
-use std::num::NonZeroU8;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{self, Debug};
+use core::num::NonZeroU8;
+use core::time::Duration;
 
 use signal_flow::*;
 
 use self::CodePoint::*;
 
+#[cfg(feature = "async")]
+pub use self::asynchronous::*;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+
 pub type Signal = bool;
 
 pub const ON: Signal = true;
@@ -206,7 +218,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         }
     }
 
-    fn set_pause(&mut self, pause: CodePoint) -> Result<(), Box<dyn Error>> {
+    fn set_pause(&mut self, pause: CodePoint) -> Result<(), FlowError> {
         assert!(pause.is_space());
 
         self.pause_duration = self.pause_duration.max(pause.duration());
@@ -215,7 +227,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         Ok(())
     }
 
-    fn flush_pause(&mut self) -> Result<(), Box<dyn Error>> {
+    fn flush_pause(&mut self) -> Result<(), FlowError> {
         self.send_pause()?;
 
         self.pause_duration = 0;
@@ -224,7 +236,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         Ok(())
     }
 
-    fn send_pause(&mut self) -> Result<(), Box<dyn Error>> {
+    fn send_pause(&mut self) -> Result<(), FlowError> {
         assert!(self.pause_written <= self.pause_duration);
 
         for _ in 0..(self.pause_duration - self.pause_written) {
@@ -236,7 +248,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         Ok(())
     }
 
-    fn send_dot_or_dash(&mut self, symbol: CodePoint) -> Result<(), Box<dyn Error>> {
+    fn send_dot_or_dash(&mut self, symbol: CodePoint) -> Result<(), FlowError> {
         assert!(symbol.is_dot_or_dash());
 
         self.flush_pause()?;
@@ -246,7 +258,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         Ok(())
     }
 
-    fn send_encoded_char(&mut self, symbols: KnownCodePoints) -> Result<(), Box<dyn Error>> {
+    fn send_encoded_char(&mut self, symbols: KnownCodePoints) -> Result<(), FlowError> {
         assert!(symbols.iter().all(|symbol| symbol.is_dot_or_dash()));
 
         for code in symbols {
@@ -257,7 +269,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         Ok(())
     }
 
-    fn send_unknown(&mut self) -> Result<(), Box<dyn Error>> {
+    fn send_unknown(&mut self) -> Result<(), FlowError> {
         self.flush_pause()?;
         self.tx.send_all(
             self.dialect
@@ -271,7 +283,7 @@ impl<D: Dialect, X: Tx<Item = Signal>> EncoderTx<D, X> {
         Ok(())
     }
 
-    fn send_char(&mut self, char: char) -> Result<(), Box<dyn Error>> {
+    fn send_char(&mut self, char: char) -> Result<(), FlowError> {
         if let Some(symbols) = self.dialect.encode_char(char) {
             // handle known char
             self.send_encoded_char(symbols)?;
@@ -305,9 +317,21 @@ impl<D: Dialect> EncoderTx<D, ()> {
 impl<D: Dialect, X: Tx<Item = Signal>> Tx for EncoderTx<D, X> {
     type Item = char;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
         self.send_char(value)
     }
+
+    /// Write the rest of the pending pause, if any, then flush inner Tx.
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.flush_pause()?;
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        let flushed = self.flush_pause();
+        let closed = self.tx.close();
+        flushed.and(closed)
+    }
 }
 
 ///////////////////////////////////////////////
@@ -339,6 +363,12 @@ impl fmt::Display for MorseDecodeError {
     }
 }
 
+impl From<MorseDecodeError> for FlowError {
+    fn from(e: MorseDecodeError) -> Self {
+        FlowError::protocol(e)
+    }
+}
+
 ///////////////////////////////////////////////
 /////////////////// Decoder ///////////////////
 ///////////////////////////////////////////////
@@ -430,6 +460,17 @@ where
     D: Dialect,
     X: Rx<Item = Signal>,
 {
+    fn read_char(&mut self, mode: RecvMode) -> Result<Option<char>, FlowError> {
+        loop {
+            let signal = mode.recv(&mut self.inner)?;
+            if let Some(char) = self.process_signal_unit(signal)? {
+                return Ok(char);
+            }
+        }
+    }
+}
+
+impl<D: Dialect, X> DecoderRx<D, X> {
     pub fn new(inner: X) -> Self {
         DecoderRx {
             inner,
@@ -441,24 +482,24 @@ where
         }
     }
 
-    fn reset_with_signal_error(&mut self) -> Box<dyn Error> {
+    fn reset_with_signal_error(&mut self) -> FlowError {
         self.reset_letter();
-        Self::boxed_error_from_group(self.reset_group())
+        Self::error_from_group(self.reset_group())
     }
 
-    fn reset_with_letter_error(&mut self) -> Box<dyn Error> {
+    fn reset_with_letter_error(&mut self) -> FlowError {
         let letter = self.current_letter.clone();
         self.reset_letter();
-        Box::new(MorseDecodeError::from_letter(letter))
+        MorseDecodeError::from_letter(letter).into()
     }
 
-    fn boxed_error_from_group(group: Option<SignalGroup>) -> Box<dyn Error> {
+    fn error_from_group(group: Option<SignalGroup>) -> FlowError {
         let signal = match group {
             Some(SignalGroup { state, duration }) => vec![state; duration.get() as usize],
             None => vec![],
         };
 
-        Box::new(MorseDecodeError::from_signal(signal))
+        MorseDecodeError::from_signal(signal).into()
     }
 
     /// Clear `current_group`, returning its old value.
@@ -471,10 +512,9 @@ where
         self.current_letter.clear();
     }
 
-    /// Read one signal unit from the signal source and return finished group (if any).
+    /// Take one signal unit read from the signal source and return finished group (if any).
     ///
     /// Algorithm:
-    ///  - Read one signal util from the signal source;
     ///  - If signal if exhausted:
     ///     * Reset current group to None, returning its old value.
     ///  - Else If signal value changed:
@@ -487,17 +527,18 @@ where
     ///         - For `ON` state, duration must be between 1 and 3 inclusive.
     ///         - For `OFF` state, duration can be any (non-zero value).
     ///     * Return None
-    fn read_signal_unit_and_update_current_group(
+    fn update_current_group(
         &mut self,
-    ) -> Result<Option<SignalGroup>, Box<dyn Error>> {
-        match self.inner.recv()? {
-            None => return Ok(self.reset_group()),
+        signal: Option<Signal>,
+    ) -> Result<Option<SignalGroup>, FlowError> {
+        match signal {
+            None => Ok(self.reset_group()),
             Some(signal) => self.add_signal_unit(signal),
         }
     }
 
     /// When this function resets the current group (because signal changed its value), it returns its old value.
-    fn add_signal_unit(&mut self, signal: Signal) -> Result<Option<SignalGroup>, Box<dyn Error>> {
+    fn add_signal_unit(&mut self, signal: Signal) -> Result<Option<SignalGroup>, FlowError> {
         Ok(match self.current_group {
             None => {
                 // New signal. Create new group, returning None.
@@ -525,7 +566,7 @@ where
         self.current_letter.push(symbol);
     }
 
-    fn decode_current_letter(&mut self) -> Result<char, Box<dyn Error>> {
+    fn decode_current_letter(&mut self) -> Result<char, FlowError> {
         match self.dialect.decode_char(&self.current_letter) {
             None => Err(self.reset_with_letter_error()),
             Some(char) => {
@@ -535,52 +576,66 @@ where
         }
     }
 
-    fn read_char(&mut self) -> Result<Option<char>, Box<dyn Error>> {
-        loop {
-            if let Some(group) = self.read_signal_unit_and_update_current_group()? {
-                if group.state == ON {
-                    let code_point = group
-                        .to_code_point()
-                        .ok_or_else(|| Self::boxed_error_from_group(Some(group)))?;
-
-                    self.add_symbol_to_letter(code_point);
-                    // TODO: check for too long letter error
-                } else {
-                    // do nothing because we have already dealt with OFF group below.
-                }
+    /// Feed one signal unit (or end of signal) into the decoder.
+    ///
+    /// Returns `Ok(None)` if more signal units are needed, or `Ok(Some(result))` where `result`
+    /// is what `recv()` should return.
+    fn process_signal_unit(
+        &mut self,
+        signal: Option<Signal>,
+    ) -> Result<Option<Option<char>>, FlowError> {
+        if let Some(group) = self.update_current_group(signal)? {
+            if group.state == ON {
+                let code_point = group
+                    .to_code_point()
+                    .ok_or_else(|| Self::error_from_group(Some(group)))?;
+
+                self.add_symbol_to_letter(code_point);
+                // TODO: check for too long letter error
             } else {
-                // Use current group instead of finished one for processing OFFs.
-                match self.current_group {
-                    Some(SignalGroup {
-                        state: OFF,
-                        duration,
-                    }) => {
-                        if duration.get() == 7 {
-                            // emit whitespace
-                            return Ok(Some(' '));
-                        } else if duration.get() == 3 {
-                            return self.decode_current_letter().map(Some);
-                        } else if duration.get() == 1 {
-                            // do nothing because dot/dash group is already converted to symbol and added to the current letter
-                            // while processing finished ON group above.
-                        }
-                    }
-                    None => {
-                        // no finished group AND current group is empty
-                        return Ok(None);
+                // do nothing because we have already dealt with OFF group below.
+            }
+        } else {
+            // Use current group instead of finished one for processing OFFs.
+            match self.current_group {
+                Some(SignalGroup {
+                    state: OFF,
+                    duration,
+                }) => {
+                    if duration.get() == 7 {
+                        // emit whitespace
+                        return Ok(Some(Some(' ')));
+                    } else if duration.get() == 3 {
+                        return self.decode_current_letter().map(|char| Some(Some(char)));
+                    } else if duration.get() == 1 {
+                        // do nothing because dot/dash group is already converted to symbol and added to the current letter
+                        // while processing finished ON group above.
                     }
-                    _ => {}
                 }
+                None => {
+                    // no finished group AND current group is empty
+                    return Ok(Some(None));
+                }
+                _ => {}
             }
         }
+        Ok(None)
     }
 }
 
 impl<D: Dialect, X: Rx<Item = Signal>> Rx for DecoderRx<D, X> {
     type Item = char;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
-        self.read_char()
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.read_char(RecvMode::Block)
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.read_char(RecvMode::Try)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        self.read_char(RecvMode::timeout(timeout))
     }
 }
 
@@ -611,6 +666,7 @@ pub trait MorseRxExt {
 
 impl<X: Rx> MorseRxExt for X {}
 
+#[cfg(feature = "std")]
 pub fn print<I: IntoIterator<Item = Signal>>(sequence: I) {
     for c in sequence.into_iter() {
         print!("{}", if c { '*' } else { ' ' });
@@ -621,6 +677,9 @@ pub fn print<I: IntoIterator<Item = Signal>>(sequence: I) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::string::String;
+    use signal_flow::rtsm::{RANGE_100_QUARTER, RtsmRxExt, RtsmTxExt};
 
     const SOS: &'static [Signal] = &[
         ON, OFF, ON, OFF, ON, // S: · · ·
@@ -656,6 +715,21 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_encode_close_pads_chunk() {
+        let mut chunks = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut chunks)
+            .chunks(5)
+            .with_partial_policy(PartialChunkPolicy::Pad(|| OFF))
+            .morse_encode::<ITU>();
+        tx.send('E').unwrap();
+        tx.close().unwrap();
+        drop(tx);
+
+        // E: · followed by letter space, padded to the chunk size
+        assert_eq!(chunks, &[[ON, OFF, OFF, OFF, OFF]]);
+    }
+
     #[test]
     fn test_whitespace() {
         assert_eq!(EncoderTx::<ITU, _>::encode_str("a b"), A_B.to_owned());
@@ -678,17 +752,99 @@ mod test {
         assert_eq!(decoded, "A B");
     }
 
+    #[test]
+    fn test_decode_replayed() {
+        let mut recording = IteratorRx::from(SOS.to_owned()).record(Vec::new()).unwrap();
+        while recording.recv().unwrap().is_some() {}
+        let bytes = recording.finish().unwrap();
+
+        let replay = ReplayRx::<Signal, _>::new(&bytes[..], Pacing::Unpaced).unwrap();
+        let decoded: String = DecoderRx::<ITU, _>::new(replay).collect().unwrap();
+        assert_eq!(decoded, "SOS");
+    }
+
+    /// Send `text` through RTSM over a simulated channel, and receive the signal back.
+    fn transmit(text: &str, conditions: ChannelConditions, seed: u64) -> Vec<Signal> {
+        let mut samples = Vec::new();
+        (&mut samples)
+            .simulate_channel(Duration::from_millis(100), conditions, seed)
+            .rtsm(RANGE_100_QUARTER)
+            .send_all(EncoderTx::<ITU, _>::encode_str(text))
+            .unwrap();
+        IteratorRx::from(samples)
+            .rtsm(RANGE_100_QUARTER)
+            .skip_errors()
+            .collect_vec()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_framed() {
+        let mut bits = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut bits)
+            .frame::<Signal>(MAX_FRAME_UNITS)
+            .morse_encode::<ITU>();
+        // flushing after every letter aligns frames to letters
+        for char in "SOS".chars() {
+            tx.send(char).unwrap();
+            tx.flush().unwrap();
+        }
+        tx.close().unwrap();
+        drop(tx);
+
+        // consumer joins in the middle of the first letter, and waits for the next frame
+        let decoded: String = IteratorRx::from(bits.split_off(20))
+            .unframe::<Signal>()
+            .morse_decode::<ITU>()
+            .collect()
+            .unwrap();
+        assert_eq!(decoded, "OS");
+    }
+
+    #[test]
+    fn test_decode_oversampled_channel() {
+        // Performance Monitor polls twice per tick, with jittery timers
+        let conditions = ChannelConditions {
+            skew: -0.5,
+            duplicate_rate: 0.1,
+            ..ChannelConditions::performance_monitor()
+        };
+        let signal = transmit("SOS SOS", conditions, 1);
+        let decoded: String = DecoderRx::<ITU, _>::new(IteratorRx::from(signal))
+            .collect()
+            .unwrap();
+        assert_eq!(decoded, "SOS SOS");
+    }
+
+    #[test]
+    fn test_bit_error_rate() {
+        let text = "THE QUICK BROWN FOX JUMPS OVER THE LAZY DOG";
+        let expected = EncoderTx::<ITU, _>::encode_str(text);
+        let lossy = ChannelConditions {
+            drop_rate: 0.02,
+            corrupt_rate: 0.01,
+            ..ChannelConditions::performance_monitor()
+        };
+        let rate = error_rate(&expected, &transmit(text, lossy, 2));
+        assert!(rate > 0.0 && rate < 0.2, "bit error rate {}", rate);
+
+        let oversampled = ChannelConditions {
+            skew: -0.5,
+            ..lossy
+        };
+        assert!(error_rate(&expected, &transmit(text, oversampled, 2)) < rate);
+    }
+
     #[test]
     fn test_decode_early_error() {
         let signal = vec![ON, ON];
         let mut coder = DecoderRx::<ITU, _>::new(IteratorRx::from(signal.clone()));
         let result = coder.recv();
         assert!(result.is_err());
-        let err = *(result
-            .err()
-            .unwrap()
-            .downcast::<MorseDecodeError>()
-            .unwrap());
-        assert_eq!(err, MorseDecodeError::from_signal(signal));
+        let err = result.err().unwrap();
+        assert_eq!(
+            err.protocol_error::<MorseDecodeError>(),
+            Some(&MorseDecodeError::from_signal(signal))
+        );
     }
 }
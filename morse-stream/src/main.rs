@@ -1,4 +1,5 @@
 use std::io::{Read, Write};
+use std::time::Duration;
 
 use morse_stream::*;
 use signal_flow::*;
@@ -12,8 +13,6 @@ enum Role {
 }
 
 mod ascii {
-    use std::error::Error;
-
     use super::*;
 
     pub const ASCII_ON: char = '-';
@@ -36,9 +35,17 @@ mod ascii {
     impl<X: Tx<Item = char>> Tx for SignalToAsciiTx<X> {
         type Item = Signal;
 
-        fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
+        fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
             self.inner.send(self.encode(value))
         }
+
+        fn flush(&mut self) -> Result<(), FlowError> {
+            self.inner.flush()
+        }
+
+        fn close(&mut self) -> Result<(), FlowError> {
+            self.inner.close()
+        }
     }
 
     pub struct SignalFromAsciiRx<X> {
@@ -61,12 +68,10 @@ mod ascii {
         }
     }
 
-    impl<X: Rx<Item = char>> Rx for SignalFromAsciiRx<X> {
-        type Item = Signal;
-
-        fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    impl<X: Rx<Item = char>> SignalFromAsciiRx<X> {
+        fn recv_with(&mut self, mode: RecvMode) -> Result<Option<Signal>, FlowError> {
             loop {
-                match self.inner.recv()? {
+                match mode.recv(&mut self.inner)? {
                     None => return Ok(None),
                     Some(char) => match self.decode(char) {
                         None => { /* loop */ }
@@ -77,6 +82,22 @@ mod ascii {
         }
     }
 
+    impl<X: Rx<Item = char>> Rx for SignalFromAsciiRx<X> {
+        type Item = Signal;
+
+        fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+            self.recv_with(RecvMode::Block)
+        }
+
+        fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+            self.recv_with(RecvMode::Try)
+        }
+
+        fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+            self.recv_with(RecvMode::timeout(timeout))
+        }
+    }
+
     pub trait AsciiRxExt
     where
         Self: Sized,
@@ -113,6 +134,7 @@ fn main() {
                 let char = byte.expect("read byte from stdin") as char;
                 encoder.send(char).expect("encode character");
             }
+            encoder.close().expect("flush encoder");
         }
         Role::Decoder => {
             let stdin = io::stdin();
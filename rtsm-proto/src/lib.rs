@@ -1,7 +1,11 @@
 //! Special encoding for morse which allows low sampling rate over
 //! short-range integer-valued signal (e.g. byte stream)
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::ops::Range;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ops::Range;
 
 pub type Signal = bool;
 
@@ -163,7 +167,7 @@ impl Default for RtsmProto<i32> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::iter;
+    use core::iter;
 
     const SIGNAL: &'static [Signal] = &[ON, OFF, ON, OFF, OFF, ON, ON, ON, OFF, OFF, OFF, OFF, OFF];
     const VALUES: &'static [i32] = &[50, 0, 50, 0, 1, 50, 51, 50, 1, 2, 0, 1, 2];
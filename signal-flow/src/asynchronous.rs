@@ -0,0 +1,448 @@
+//! Asynchronous counterparts of `Tx` and `Rx`, and adapters to/from `futures` `Sink` and `Stream`.
+//!
+//! Stateless adapters (`Interval`, `ChunksTx`, `MapRx`, `FuseRx`) are shared with the blocking
+//! API and implement both flavours of the traits. Adapters whose type depends on the inner
+//! trait get an `Async` prefixed twin.
+use std::error::Error;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use super::*;
+
+pub trait AsyncTx {
+    type Item;
+
+    /// Asynchronously send value.
+    fn send(&mut self, value: Self::Item) -> impl Future<Output = Result<(), FlowError>>;
+
+    /// Asynchronous counterpart of `Tx::flush`.
+    fn flush(&mut self) -> impl Future<Output = Result<(), FlowError>> {
+        async { Ok(()) }
+    }
+
+    /// Asynchronous counterpart of `Tx::close`.
+    fn close(&mut self) -> impl Future<Output = Result<(), FlowError>> {
+        self.flush()
+    }
+
+    fn interval(self, rate: Duration) -> Interval<Self, IntervalRoleTx>
+    where
+        Self: Sized,
+    {
+        Interval::new(self, rate)
+    }
+
+    fn interval_with_clock<C: Clock>(
+        self,
+        rate: Duration,
+        clock: C,
+    ) -> Interval<Self, IntervalRoleTx, C>
+    where
+        Self: Sized,
+    {
+        Interval::with_clock(self, rate, clock)
+    }
+
+    fn chunks<T>(self, chunk_size: usize) -> ChunksTx<Self, T>
+    where
+        Self: Sized,
+    {
+        ChunksTx::new(chunk_size, self)
+    }
+
+    fn instrument(self, name: &str) -> InstrumentedTx<Self>
+    where
+        Self: Sized,
+    {
+        InstrumentedTx::new(self, MetricsRegistry::global().register(name))
+    }
+
+    fn instrument_in<C: Clock + Clone>(
+        self,
+        name: &str,
+        registry: &MetricsRegistry<C>,
+    ) -> InstrumentedTx<Self, C>
+    where
+        Self: Sized,
+    {
+        InstrumentedTx::new(self, registry.register(name))
+    }
+
+    /// Turn this `AsyncTx` into a `futures::Sink`.
+    fn into_sink(self) -> impl Sink<Self::Item, Error = FlowError>
+    where
+        Self: Sized,
+    {
+        futures::sink::unfold(self, |mut tx, value| async move {
+            tx.send(value).await?;
+            Ok(tx)
+        })
+    }
+}
+
+pub trait AsyncRx {
+    type Item;
+
+    /// Asynchronously receive value.
+    fn recv(&mut self) -> impl Future<Output = Result<Option<Self::Item>, FlowError>>;
+
+    fn deduplicate(self) -> AsyncDeduplicateRx<Self>
+    where
+        Self: Sized,
+    {
+        AsyncDeduplicateRx::new(self)
+    }
+
+    fn fuse(self) -> FuseRx<Self>
+    where
+        Self: Sized,
+    {
+        FuseRx::new(self)
+    }
+
+    fn interval(self, rate: Duration) -> Interval<Self, IntervalRoleRx>
+    where
+        Self: Sized,
+    {
+        Interval::new(self, rate)
+    }
+
+    fn interval_with_clock<C: Clock>(
+        self,
+        rate: Duration,
+        clock: C,
+    ) -> Interval<Self, IntervalRoleRx, C>
+    where
+        Self: Sized,
+    {
+        Interval::with_clock(self, rate, clock)
+    }
+
+    fn map<F>(self, f: F) -> MapRx<Self, F>
+    where
+        Self: Sized,
+    {
+        MapRx::new(self, f)
+    }
+
+    fn instrument(self, name: &str) -> InstrumentedRx<Self>
+    where
+        Self: Sized,
+    {
+        InstrumentedRx::new(self, MetricsRegistry::global().register(name))
+    }
+
+    fn instrument_in<C: Clock + Clone>(
+        self,
+        name: &str,
+        registry: &MetricsRegistry<C>,
+    ) -> InstrumentedRx<Self, C>
+    where
+        Self: Sized,
+    {
+        InstrumentedRx::new(self, registry.register(name))
+    }
+
+    fn collect_vec(mut self) -> impl Future<Output = Result<Vec<Self::Item>, FlowError>>
+    where
+        Self: Sized,
+    {
+        async move {
+            let mut vec = Vec::new();
+            while let Some(item) = self.recv().await? {
+                vec.push(item);
+            }
+            Ok(vec)
+        }
+    }
+
+    /// Turn this `AsyncRx` into a `futures::Stream`.
+    ///
+    /// Errors are yielded as items, and the stream ends when `recv()` returns `Ok(None)`.
+    fn into_stream(self) -> impl Stream<Item = Result<Self::Item, FlowError>>
+    where
+        Self: Sized,
+    {
+        futures::stream::unfold(self, |mut rx| async move {
+            match rx.recv().await {
+                Ok(Some(item)) => Some((Ok(item), rx)),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), rx)),
+            }
+        })
+    }
+}
+
+////////////////////////////////////////////////
+//////////////// Sink / Stream /////////////////
+////////////////////////////////////////////////
+
+/// Sends values into a `futures::Sink`.
+pub struct SinkTx<S, T> {
+    sink: S,
+    _marker: PhantomData<T>,
+}
+
+impl<S, T> SinkTx<S, T>
+where
+    S: Sink<T> + Unpin,
+{
+    pub fn new(sink: S) -> Self {
+        SinkTx {
+            sink,
+            _marker: Default::default(),
+        }
+    }
+}
+
+impl<S, T> AsyncTx for SinkTx<S, T>
+where
+    S: Sink<T> + Unpin,
+    S::Error: Error + Send + Sync + 'static,
+{
+    type Item = T;
+
+    async fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.sink.send(value).await.map_err(FlowError::other)
+    }
+
+    async fn flush(&mut self) -> Result<(), FlowError> {
+        self.sink.flush().await.map_err(FlowError::other)
+    }
+
+    async fn close(&mut self) -> Result<(), FlowError> {
+        self.sink.close().await.map_err(FlowError::other)
+    }
+}
+
+/// Receives values from a `futures::Stream`. Never returns an error.
+pub struct StreamRx<S> {
+    stream: S,
+}
+
+impl<S> StreamRx<S>
+where
+    S: Stream + Unpin,
+{
+    pub fn new(stream: S) -> Self {
+        StreamRx { stream }
+    }
+}
+
+impl<S> AsyncRx for StreamRx<S>
+where
+    S: Stream + Unpin,
+{
+    type Item = S::Item;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.stream.next().await)
+    }
+}
+
+impl<S> From<S> for StreamRx<S>
+where
+    S: Stream + Unpin,
+{
+    fn from(from: S) -> Self {
+        Self::new(from)
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Adapters ///////////////////
+////////////////////////////////////////////////
+
+impl<T: AsyncTx, C: Clock> AsyncTx for Interval<T, IntervalRoleTx, C> {
+    type Item = T::Item;
+
+    async fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.delay_for_tick().await?;
+        self.inner.send(value).await
+    }
+
+    async fn flush(&mut self) -> Result<(), FlowError> {
+        self.inner.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), FlowError> {
+        self.inner.close().await
+    }
+}
+
+impl<R: AsyncRx, C: Clock> AsyncRx for Interval<R, IntervalRoleRx, C> {
+    type Item = R::Item;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.delay_for_tick().await?;
+        self.inner.recv().await
+    }
+}
+
+impl<X, T> AsyncTx for ChunksTx<X, T>
+where
+    X: AsyncTx<Item = Vec<T>>,
+{
+    type Item = T;
+
+    async fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if let Some(vec) = self.push(value) {
+            self.tx.send(vec).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), FlowError> {
+        if let Some(vec) = self.take_partial() {
+            self.tx.send(vec).await?;
+        }
+        self.tx.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), FlowError> {
+        let sent = match self.take_partial() {
+            Some(vec) => self.tx.send(vec).await,
+            None => Ok(()),
+        };
+        let closed = self.tx.close().await;
+        sent.and(closed)
+    }
+}
+
+impl<R: AsyncRx, F, U> AsyncRx for MapRx<R, F>
+where
+    F: FnMut(R::Item) -> U,
+{
+    type Item = U;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.inner.recv().await?.map(&mut self.f))
+    }
+}
+
+impl<R: AsyncRx> AsyncRx for FuseRx<R> {
+    type Item = R::Item;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        if self.error {
+            Ok(None)
+        } else {
+            match self.inner.recv().await {
+                Ok(item) => Ok(item),
+                Err(_) => {
+                    self.error = true;
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+impl<X: AsyncTx, C: Clock> AsyncTx for InstrumentedTx<X, C> {
+    type Item = X::Item;
+
+    async fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.send(value).await;
+        self.recorder.record_send(started, &result);
+        result
+    }
+
+    async fn flush(&mut self) -> Result<(), FlowError> {
+        self.inner.flush().await
+    }
+
+    async fn close(&mut self) -> Result<(), FlowError> {
+        self.inner.close().await
+    }
+}
+
+impl<R: AsyncRx, C: Clock> AsyncRx for InstrumentedRx<R, C> {
+    type Item = R::Item;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.recv().await;
+        self.recorder.record_recv(started, &result);
+        result
+    }
+}
+
+/// Asynchronous twin of `DeduplicateRx`.
+pub struct AsyncDeduplicateRx<R: AsyncRx> {
+    inner: R,
+    last: Option<Option<R::Item>>,
+}
+
+impl<R: AsyncRx> AsyncDeduplicateRx<R> {
+    pub fn new(inner: R) -> Self {
+        AsyncDeduplicateRx { inner, last: None }
+    }
+}
+
+impl<R> AsyncRx for AsyncDeduplicateRx<R>
+where
+    R: AsyncRx,
+    R::Item: Clone + Eq,
+{
+    type Item = R::Item;
+
+    async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        let new = match self.last.clone() {
+            None => {
+                // first time here
+                self.inner.recv().await?
+            }
+            Some(last) => {
+                let mut new = last.clone();
+                while new == last {
+                    new = self.inner.recv().await?;
+                }
+                // at this point new != last
+                new
+            }
+        };
+
+        self.last = Some(new.clone());
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_sink_stream() {
+        block_on(async {
+            let (sender, receiver) = mpsc::unbounded();
+            let mut tx = SinkTx::new(sender).chunks(2);
+            for i in 0..5 {
+                tx.send(i).await.unwrap();
+            }
+            // closing the sink ends the stream, and emits the partial chunk
+            tx.close().await.unwrap();
+
+            let rx = StreamRx::new(receiver).map(|vec: Vec<i32>| vec.iter().sum::<i32>());
+            assert_eq!(rx.collect_vec().await.unwrap(), &[1, 5, 4]);
+        });
+    }
+
+    #[test]
+    fn test_deduplicate_into_stream() {
+        block_on(async {
+            let registry = MetricsRegistry::new();
+            let rx = StreamRx::new(futures::stream::iter(vec![1, 1, 2, 2, 2, 3, 1]))
+                .instrument_in("source", &registry);
+            let stream = rx.deduplicate().into_stream();
+            let vec = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+            assert_eq!(vec, &[1, 2, 3, 1]);
+            assert_eq!(registry.stage("source").unwrap().stats.items, 7);
+        });
+    }
+}
@@ -0,0 +1,159 @@
+//! Cancellation token shared between a worker and whoever wants to stop it.
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Clonable flag which can be set once, and then stays set. Clones refer to the same flag.
+///
+/// Besides polling, one may block on `wait()` until it is set. Blocking adapters which accept
+/// a token (pair receivers, `Interval`) are woken up as soon as it is cancelled.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    node: Arc<Node>,
+}
+
+#[derive(Default)]
+struct Node {
+    cancelled: AtomicBool,
+    links: Mutex<Links>,
+    cancelled_cond: Condvar,
+}
+
+#[derive(Default)]
+struct Links {
+    children: Vec<Weak<Node>>,
+    wakers: Vec<Weak<dyn Wake>>,
+}
+
+/// Something blocked on its own condition variable which must re-check the token.
+pub(crate) trait Wake: Send + Sync {
+    fn wake(&self);
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the flag, waking up everyone waiting on this token or any of its children.
+    pub fn cancel(&self) {
+        self.node.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.node.cancelled.load(Ordering::Acquire)
+    }
+
+    /// New token which is cancelled together with this one, but may also be cancelled on its
+    /// own without affecting this one.
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut links = self.node.links.lock().unwrap();
+        if self.is_cancelled() {
+            child.node.cancelled.store(true, Ordering::Release);
+        } else {
+            links.children.retain(|child| child.strong_count() > 0);
+            links.children.push(Arc::downgrade(&child.node));
+        }
+        child
+    }
+
+    /// Block until the token is cancelled.
+    pub fn wait(&self) {
+        let mut links = self.node.links.lock().unwrap();
+        while !self.is_cancelled() {
+            links = self.node.cancelled_cond.wait(links).unwrap();
+        }
+    }
+
+    /// Block until the token is cancelled, but at most for `timeout`. Returns whether the token
+    /// is cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut links = self.node.links.lock().unwrap();
+        while !self.is_cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            links = self
+                .node
+                .cancelled_cond
+                .wait_timeout(links, deadline - now)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+
+    /// Call `waker.wake()` upon cancellation, for as long as it is alive. Callers must check
+    /// `is_cancelled()` after registration, as the token might have been cancelled already.
+    pub(crate) fn register(&self, waker: Weak<dyn Wake>) {
+        let mut links = self.node.links.lock().unwrap();
+        links.wakers.retain(|waker| waker.strong_count() > 0);
+        links.wakers.push(waker);
+    }
+}
+
+impl Node {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let links = {
+            let mut links = self.links.lock().unwrap();
+            self.cancelled_cond.notify_all();
+            std::mem::take(&mut *links)
+        };
+        for waker in links.wakers.iter().filter_map(Weak::upgrade) {
+            waker.wake();
+        }
+        for child in links.children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_child_tokens() {
+        let parent = CancellationToken::new();
+        let child = parent.child_token();
+        let grandchild = child.child_token();
+
+        child.cancel();
+        assert!(grandchild.is_cancelled());
+        assert!(!parent.is_cancelled());
+
+        let sibling = parent.child_token();
+        parent.cancel();
+        assert!(sibling.is_cancelled());
+        // children of cancelled tokens are born cancelled
+        assert!(parent.child_token().is_cancelled());
+    }
+
+    #[test]
+    fn test_wait() {
+        let token = CancellationToken::new();
+        assert!(!token.wait_timeout(Duration::from_millis(1)));
+
+        let child = token.child_token();
+        let waiter = thread::spawn(move || child.wait());
+        token.cancel();
+        waiter.join().unwrap();
+        assert!(token.wait_timeout(Duration::from_secs(3600)));
+    }
+}
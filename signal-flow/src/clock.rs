@@ -0,0 +1,100 @@
+//! Time source for rate-limited adapters like `Interval`.
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancellationToken;
+
+/// Source of monotonic time which is also able to wait for some time to pass.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Block current thread until `duration` passes according to this clock.
+    fn sleep(&self, duration: Duration);
+
+    /// Like `sleep`, but return early once `token` is cancelled.
+    ///
+    /// Default implementation only skips sleeping if the token is cancelled already.
+    fn sleep_cancellable(&self, duration: Duration, token: &CancellationToken) {
+        if !token.is_cancelled() {
+            self.sleep(duration);
+        }
+    }
+
+    /// Asynchronous counterpart of `sleep`.
+    #[cfg(feature = "async")]
+    fn delay(&self, duration: Duration) -> impl std::future::Future<Output = ()> {
+        futures_timer::Delay::new(duration)
+    }
+}
+
+/// Real monotonic clock backed by `std::time::Instant` and `std::thread::sleep`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        sleep(duration);
+    }
+
+    fn sleep_cancellable(&self, duration: Duration, token: &CancellationToken) {
+        token.wait_timeout(duration);
+    }
+}
+
+/// Clock for tests which only moves forward when told so.
+///
+/// Sleeping on this clock advances it by the requested duration immediately, so pipelines
+/// with intervals run as fast as possible while still observing consistent timestamps.
+/// Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct VirtualClock {
+    start: Instant,
+    now: Arc<Mutex<Instant>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        let start = Instant::now();
+        VirtualClock {
+            start,
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    /// Move the time forward.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+
+    /// Total time advanced since this clock was created.
+    pub fn elapsed(&self) -> Duration {
+        self.now() - self.start
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    #[cfg(feature = "async")]
+    async fn delay(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
@@ -0,0 +1,177 @@
+//! Error type shared by all `Tx`/`Rx` implementations.
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::sync::mpsc::SendError;
+
+#[cfg(feature = "std")]
+use crate::interval::LateTickError;
+
+/// Everything that could go wrong while sending or receiving values.
+///
+/// Kinds of failures which callers usually need to tell apart (cancellation, hung up channels,
+/// timeouts) have dedicated variants. Failures specific to some adapter (e.g. RTSM
+/// `DecodeError` or Morse `MorseDecodeError`) are wrapped as `Protocol` and can be inspected
+/// via `protocol_error()` or `Error::source()`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FlowError {
+    /// Chain was cancelled by a cancellation token.
+    Cancelled,
+    /// The other side of a channel hung up, so the value could not be delivered.
+    Disconnected,
+    /// Non-blocking send found the channel full. The value is dropped.
+    Full,
+    /// Non-blocking receive found no value available yet.
+    Empty,
+    /// No value arrived before the timeout expired.
+    Timeout,
+    /// Interval call was late for its tick.
+    #[cfg(feature = "std")]
+    Late(LateTickError),
+    /// Values violate the protocol of some encoding/decoding adapter.
+    Protocol(Box<dyn Error + Send + Sync>),
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// Anything else, e.g. failures of user-supplied closures.
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+impl FlowError {
+    pub fn protocol<E: Into<Box<dyn Error + Send + Sync>>>(error: E) -> Self {
+        FlowError::Protocol(error.into())
+    }
+
+    pub fn other<E: Into<Box<dyn Error + Send + Sync>>>(error: E) -> Self {
+        FlowError::Other(error.into())
+    }
+
+    /// Concrete protocol error, if this is a `Protocol` error of type `E`.
+    pub fn protocol_error<E: Error + 'static>(&self) -> Option<&E> {
+        match self {
+            FlowError::Protocol(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, FlowError::Cancelled)
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, FlowError::Disconnected)
+    }
+
+    /// Whether `try_recv()` or `recv_timeout()` found no value in time, so it is worth polling
+    /// again later.
+    pub fn is_not_ready(&self) -> bool {
+        matches!(self, FlowError::Empty | FlowError::Timeout)
+    }
+
+    pub fn is_protocol(&self) -> bool {
+        matches!(self, FlowError::Protocol(_))
+    }
+}
+
+impl fmt::Display for FlowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlowError::Cancelled => "cancelled by cancellation token".fmt(f),
+            FlowError::Disconnected => "sending on a closed channel".fmt(f),
+            FlowError::Full => "sending on a full channel".fmt(f),
+            FlowError::Empty => "receiving on an empty channel".fmt(f),
+            FlowError::Timeout => "timed out waiting on channel".fmt(f),
+            #[cfg(feature = "std")]
+            FlowError::Late(e) => e.fmt(f),
+            FlowError::Protocol(e) => write!(f, "protocol error: {}", e),
+            #[cfg(feature = "std")]
+            FlowError::Io(e) => write!(f, "I/O error: {}", e),
+            FlowError::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for FlowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            FlowError::Late(e) => Some(e),
+            FlowError::Protocol(e) => Some(&**e),
+            #[cfg(feature = "std")]
+            FlowError::Io(e) => Some(e),
+            FlowError::Other(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<LateTickError> for FlowError {
+    fn from(e: LateTickError) -> Self {
+        FlowError::Late(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for FlowError {
+    fn from(e: io::Error) -> Self {
+        FlowError::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> From<SendError<T>> for FlowError {
+    fn from(_: SendError<T>) -> Self {
+        FlowError::Disconnected
+    }
+}
+
+impl From<&str> for FlowError {
+    fn from(message: &str) -> Self {
+        FlowError::Other(message.into())
+    }
+}
+
+impl From<String> for FlowError {
+    fn from(message: String) -> Self {
+        FlowError::Other(message.into())
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for FlowError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        FlowError::Other(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtsm::DecodeError;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_protocol_source() {
+        let err = FlowError::from(DecodeError(42u32));
+        assert!(err.is_protocol());
+        assert_eq!(err.protocol_error::<DecodeError<u32>>().unwrap().0, 42);
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "failed to decode a signal");
+        assert!(FlowError::Cancelled.source().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_conversions() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        drop(receiver);
+        assert!(FlowError::from(sender.send(1).unwrap_err()).is_disconnected());
+        assert!(matches!(FlowError::from("oops"), FlowError::Other(_)));
+        let io = io::Error::new(io::ErrorKind::BrokenPipe, "pipe");
+        assert!(matches!(FlowError::from(io), FlowError::Io(_)));
+    }
+}
@@ -0,0 +1,358 @@
+//! Forward error correction of bit streams, e.g. RTSM signals.
+//!
+//! Encoder and decoder work on blocks of a fixed size, so they must agree on the code and stay
+//! aligned: a lost or duplicated bit shifts all the blocks after it. Use FEC over channels which
+//! corrupt bits but keep their count, e.g. RTSM with `LostSamplePolicy::Reconstruct`.
+use alloc::collections::VecDeque;
+
+use super::*;
+use crate::rtsm::{OFF, Signal};
+
+/// Error correcting code of `FecEncodeTx` and `FecDecodeRx`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FecCode {
+    /// Every bit is sent `n` times, and decoded by majority vote. Corrects up to `n / 2` errors
+    /// per bit. `n` must be odd.
+    Repetition(usize),
+    /// Every 4 bits are sent as a 7 bit Hamming codeword, which corrects a single error.
+    /// Codewords are interleaved `depth` at a time, i.e. their first bits are sent first, then
+    /// their second bits and so on, so that a burst of up to `depth` errors is corrected too.
+    Hamming74 { depth: usize },
+}
+
+impl FecCode {
+    fn validate(self) {
+        match self {
+            FecCode::Repetition(n) => assert!(n % 2 == 1, "Repetition count must be odd"),
+            FecCode::Hamming74 { depth } => assert_ne!(depth, 0, "Depth must not be zero"),
+        }
+    }
+
+    /// Number of data bits in a block.
+    pub fn data_bits(self) -> usize {
+        match self {
+            FecCode::Repetition(_) => 1,
+            FecCode::Hamming74 { depth } => 4 * depth,
+        }
+    }
+
+    /// Number of bits a block is sent as.
+    pub fn code_bits(self) -> usize {
+        match self {
+            FecCode::Repetition(n) => n,
+            FecCode::Hamming74 { depth } => 7 * depth,
+        }
+    }
+
+    fn encode(self, data: &[Signal], out: &mut Vec<Signal>) {
+        match self {
+            FecCode::Repetition(n) => out.extend(core::iter::repeat_n(data[0], n)),
+            FecCode::Hamming74 { .. } => {
+                let codewords: Vec<_> = data.chunks(4).map(hamming_encode).collect();
+                for i in 0..7 {
+                    out.extend(codewords.iter().map(|codeword| codeword[i]));
+                }
+            }
+        }
+    }
+
+    /// Decode a block into `out`, and return the number of corrected bits.
+    fn decode(self, code: &[Signal], out: &mut VecDeque<Signal>) -> usize {
+        match self {
+            FecCode::Repetition(n) => {
+                let ones = code.iter().filter(|bit| **bit).count();
+                let bit = ones > n / 2;
+                out.push_back(bit);
+                if bit { n - ones } else { ones }
+            }
+            FecCode::Hamming74 { depth } => {
+                let mut corrected = 0;
+                for j in 0..depth {
+                    let mut codeword = [OFF; 7];
+                    for (i, bit) in codeword.iter_mut().enumerate() {
+                        *bit = code[i * depth + j];
+                    }
+                    corrected += hamming_decode(&mut codeword) as usize;
+                    out.extend([codeword[2], codeword[4], codeword[5], codeword[6]]);
+                }
+                corrected
+            }
+        }
+    }
+}
+
+/// Codeword `p1 p2 d1 p3 d2 d3 d4` of 4 data bits.
+fn hamming_encode(data: &[Signal]) -> [Signal; 7] {
+    let [d1, d2, d3, d4] = [data[0], data[1], data[2], data[3]];
+    [d1 ^ d2 ^ d4, d1 ^ d3 ^ d4, d1, d2 ^ d3 ^ d4, d2, d3, d4]
+}
+
+/// Correct a single error in the codeword in place, and tell whether there was one.
+fn hamming_decode(codeword: &mut [Signal; 7]) -> bool {
+    // parity bit `k` covers positions which have bit `k` set, counting from 1
+    let syndrome: usize = (0..3)
+        .filter(|k| {
+            let covered = (1..=7).filter(|position| position >> k & 1 == 1);
+            covered.fold(false, |parity, position| parity ^ codeword[position - 1])
+        })
+        .map(|k| 1 << k)
+        .sum();
+    if syndrome != 0 {
+        codeword[syndrome - 1] ^= true;
+    }
+    syndrome != 0
+}
+
+/// What `FecDecodeRx` has decoded so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FecStats {
+    pub blocks: u64,
+    /// Bits which were received wrong, and corrected. Uncorrectable errors are not counted.
+    pub corrected: u64,
+}
+
+////////////////////////////////////////////////
+//////////////////// Encode ////////////////////
+////////////////////////////////////////////////
+
+/// Encodes sent bits with `FecCode`. Incomplete block is padded with `OFF` on flush and close.
+pub struct FecEncodeTx<X> {
+    tx: X,
+    code: FecCode,
+    buffer: Vec<Signal>,
+}
+
+impl<X: Tx<Item = Signal>> FecEncodeTx<X> {
+    pub fn new(code: FecCode, tx: X) -> Self {
+        code.validate();
+        FecEncodeTx {
+            tx,
+            code,
+            buffer: Vec::with_capacity(code.data_bits()),
+        }
+    }
+
+    fn send_block(&mut self) -> Result<(), FlowError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.buffer.resize(self.code.data_bits(), OFF);
+        let mut bits = Vec::with_capacity(self.code.code_bits());
+        self.code.encode(&self.buffer, &mut bits);
+        self.buffer.clear();
+        for bit in bits {
+            self.tx.send(bit)?;
+        }
+        Ok(())
+    }
+}
+
+impl<X: Tx<Item = Signal>> Tx for FecEncodeTx<X> {
+    type Item = Signal;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.buffer.push(value);
+        if self.buffer.len() == self.code.data_bits() {
+            self.send_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.send_block()?;
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        let sent = self.send_block();
+        let closed = self.tx.close();
+        sent.and(closed)
+    }
+}
+
+////////////////////////////////////////////////
+//////////////////// Decode ////////////////////
+////////////////////////////////////////////////
+
+/// Decodes received bits with `FecCode`, correcting errors where possible. Incomplete block at
+/// the end of input is lost.
+pub struct FecDecodeRx<R> {
+    rx: R,
+    code: FecCode,
+    block: Vec<Signal>,
+    ready: VecDeque<Signal>,
+    stats: FecStats,
+}
+
+impl<R: Rx<Item = Signal>> FecDecodeRx<R> {
+    pub fn new(code: FecCode, rx: R) -> Self {
+        code.validate();
+        FecDecodeRx {
+            rx,
+            code,
+            block: Vec::with_capacity(code.code_bits()),
+            ready: VecDeque::new(),
+            stats: FecStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> FecStats {
+        self.stats
+    }
+}
+
+impl<R: Rx<Item = Signal>> Rx for FecDecodeRx<R> {
+    type Item = Signal;
+
+    imp_recv_modes!(self, mode => {
+        loop {
+            if let Some(bit) = self.ready.pop_front() {
+                return Ok(Some(bit));
+            }
+            match mode.recv(&mut self.rx)? {
+                None => return Ok(None),
+                Some(bit) => self.block.push(bit),
+            }
+            if self.block.len() == self.code.code_bits() {
+                let corrected = self.code.decode(&self.block, &mut self.ready);
+                self.block.clear();
+                self.stats.blocks += 1;
+                self.stats.corrected += corrected as u64;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtsm::ON;
+    #[cfg(feature = "std")]
+    use std::time::Duration;
+
+    fn encode(code: FecCode, data: &[Signal]) -> Vec<Signal> {
+        let mut bits = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut bits).fec_encode(code);
+        tx.send_all(data.iter().copied()).unwrap();
+        tx.close().unwrap();
+        drop(tx);
+        bits
+    }
+
+    fn decode(code: FecCode, bits: Vec<Signal>) -> (Vec<Signal>, FecStats) {
+        let mut rx = IteratorRx::from(bits).fec_decode(code);
+        let data = (&mut rx).collect_vec().unwrap();
+        (data, rx.stats())
+    }
+
+    #[cfg(feature = "std")]
+    fn random_bits(len: usize, seed: u64) -> Vec<Signal> {
+        let mut rng = SimRng::new(seed);
+        (0..len).map(|_| rng.chance(0.5)).collect()
+    }
+
+    #[test]
+    fn test_hamming() {
+        let code = FecCode::Hamming74 { depth: 1 };
+        for word in 0..16u8 {
+            let data: Vec<_> = (0..4).map(|i| word >> i & 1 == 1).collect();
+            let bits = encode(code, &data);
+            for error in 0..7 {
+                let mut bits = bits.clone();
+                bits[error] ^= true;
+                assert_eq!(
+                    decode(code, bits),
+                    (
+                        data.clone(),
+                        FecStats {
+                            blocks: 1,
+                            corrected: 1
+                        }
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_interleaving() {
+        let code = FecCode::Hamming74 { depth: 8 };
+        let data = random_bits(64, 1);
+        let mut bits = encode(code, &data);
+        assert_eq!(bits.len(), 2 * 7 * 8);
+        // bursts as long as the depth
+        for bit in &mut bits[10..18] {
+            *bit ^= true;
+        }
+        for bit in &mut bits[70..78] {
+            *bit ^= true;
+        }
+        let (decoded, stats) = decode(code, bits);
+        assert_eq!(decoded, data);
+        assert_eq!(stats.corrected, 16);
+    }
+
+    #[test]
+    fn test_repetition() {
+        let code = FecCode::Repetition(3);
+        let data = [ON, OFF, ON];
+        let mut bits = encode(code, &data);
+        assert_eq!(bits, &[ON, ON, ON, OFF, OFF, OFF, ON, ON, ON]);
+        bits[1] = OFF;
+        bits[5] = ON;
+        let (decoded, stats) = decode(code, bits);
+        assert_eq!(decoded, data);
+        assert_eq!(stats.corrected, 2);
+    }
+
+    #[test]
+    fn test_partial_block() {
+        let code = FecCode::Hamming74 { depth: 2 };
+        let (decoded, _) = decode(code, encode(code, &[ON, ON, ON]));
+        assert_eq!(decoded, &[ON, ON, ON, OFF, OFF, OFF, OFF, OFF]);
+    }
+
+    /// Bit error rate of `data` sent with `code` over a channel which flips bits.
+    #[cfg(feature = "std")]
+    fn channel_error_rate(code: Option<FecCode>, data: &[Signal], seed: u64) -> f64 {
+        let noisy = ChannelConditions {
+            corrupt_rate: 0.02,
+            ..ChannelConditions::default()
+        };
+        let mut bits = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut bits).simulate_channel(
+            Duration::from_millis(100),
+            noisy,
+            seed,
+        );
+        let values = data.iter().copied();
+        match code {
+            Some(code) => tx.fec_encode(code).send_all(values).unwrap(),
+            None => tx.send_all(values).unwrap(),
+        }
+        let received = match code {
+            Some(code) => decode(code, bits).0,
+            None => bits,
+        };
+        error_rate(data, &received[..data.len()])
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_error_rate() {
+        let data = random_bits(2000, 2);
+        let raw = channel_error_rate(None, &data, 3);
+        assert!(raw > 0.01, "raw error rate {}", raw);
+        for code in [FecCode::Repetition(3), FecCode::Hamming74 { depth: 4 }] {
+            let rate = channel_error_rate(Some(code), &data, 3);
+            assert!(
+                rate < raw / 3.0,
+                "{:?}: error rate {} vs {}",
+                code,
+                rate,
+                raw
+            );
+        }
+    }
+}
@@ -0,0 +1,442 @@
+//! Framing of bit streams, e.g. RTSM signals, into checked frames.
+//!
+//! A frame is sent as bits, most significant first:
+//!
+//! ```text
+//! | preamble: 16 | length: 8 | sequence number: 8 | payload: length units | checksum: 8 or 16 |
+//! ```
+//!
+//! Checksum covers everything between the preamble and itself. Receiver hunts for the preamble,
+//! so that it may join a stream at any point, and skips frames which fail the checksum.
+use alloc::collections::VecDeque;
+use core::error::Error;
+use core::fmt;
+
+use super::*;
+use crate::rtsm::Signal;
+
+/// Sync pattern at the start of every frame.
+pub const PREAMBLE: u16 = 0b1010_1010_0111_1110;
+
+/// Maximum number of payload units in a frame.
+pub const MAX_FRAME_UNITS: usize = u8::MAX as usize;
+
+const HEADER_BITS: usize = 16;
+
+/// Unit of frame payload, e.g. a single Morse signal or a byte.
+pub trait FrameUnit: Sized {
+    const BITS: usize;
+
+    fn push_bits(&self, bits: &mut Vec<Signal>);
+
+    /// `bits` are exactly `BITS` long.
+    fn from_bits(bits: &[Signal]) -> Self;
+}
+
+impl FrameUnit for bool {
+    const BITS: usize = 1;
+
+    fn push_bits(&self, bits: &mut Vec<Signal>) {
+        bits.push(*self);
+    }
+
+    fn from_bits(bits: &[Signal]) -> Self {
+        bits[0]
+    }
+}
+
+impl FrameUnit for u8 {
+    const BITS: usize = 8;
+
+    fn push_bits(&self, bits: &mut Vec<Signal>) {
+        push_int(bits, *self as u32, 8);
+    }
+
+    fn from_bits(bits: &[Signal]) -> Self {
+        read_int(bits) as u8
+    }
+}
+
+fn push_int(bits: &mut Vec<Signal>, value: u32, width: usize) {
+    bits.extend((0..width).rev().map(|i| value >> i & 1 == 1));
+}
+
+fn read_int(bits: &[Signal]) -> u32 {
+    bits.iter().fold(0, |value, &bit| value << 1 | bit as u32)
+}
+
+fn preamble() -> Vec<Signal> {
+    let mut bits = Vec::with_capacity(16);
+    push_int(&mut bits, PREAMBLE as u32, 16);
+    bits
+}
+
+/// Cyclic redundancy check of frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Checksum {
+    /// CRC-8/SMBUS: polynomial 0x07, initial value 0.
+    Crc8,
+    /// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF.
+    #[default]
+    Crc16,
+}
+
+impl Checksum {
+    pub fn bits(self) -> usize {
+        match self {
+            Checksum::Crc8 => 8,
+            Checksum::Crc16 => 16,
+        }
+    }
+
+    pub fn compute(self, bits: &[Signal]) -> u32 {
+        let (poly, init) = match self {
+            Checksum::Crc8 => (0x07, 0),
+            Checksum::Crc16 => (0x1021, 0xFFFF),
+        };
+        let width = self.bits();
+        let mask = (1 << width) - 1;
+        bits.iter().fold(init, |crc: u32, &bit| {
+            let feedback = (crc >> (width - 1) & 1 == 1) != bit;
+            let crc = crc << 1 & mask;
+            if feedback { crc ^ poly } else { crc }
+        })
+    }
+}
+
+/// Bad news from `FrameRx`. Receiving may go on after them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FramingError {
+    /// Frame failed the checksum, and was skipped.
+    Corrupt,
+    /// Sequence numbers tell that this many frames were missed.
+    Dropped(usize),
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::Corrupt => "frame failed the checksum".fmt(f),
+            FramingError::Dropped(n) => write!(f, "{} frames were dropped", n),
+        }
+    }
+}
+
+impl Error for FramingError {}
+
+/// What `FrameRx` has received so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames which passed the checksum.
+    pub frames: u64,
+    pub dropped: u64,
+    pub corrupt: u64,
+}
+
+////////////////////////////////////////////////
+/////////////////// Frame Tx ///////////////////
+////////////////////////////////////////////////
+
+/// Packs units into frames of up to `max_len` units, and sends them bit by bit. Incomplete frame
+/// is sent on flush and close.
+pub struct FrameTx<X, U> {
+    tx: X,
+    max_len: usize,
+    checksum: Checksum,
+    seq: u8,
+    buffer: Vec<U>,
+}
+
+impl<X, U> FrameTx<X, U>
+where
+    X: Tx<Item = Signal>,
+    U: FrameUnit,
+{
+    pub fn new(max_len: usize, tx: X) -> Self {
+        assert!(
+            (1..=MAX_FRAME_UNITS).contains(&max_len),
+            "Frame length must be within 1..={}",
+            MAX_FRAME_UNITS
+        );
+        FrameTx {
+            tx,
+            max_len,
+            checksum: Checksum::default(),
+            seq: 0,
+            buffer: Vec::with_capacity(max_len),
+        }
+    }
+
+    /// Set the checksum. Default is `Checksum::Crc16`. Receiver must use the same one.
+    pub fn with_checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    fn send_frame(&mut self) -> Result<(), FlowError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let mut bits = Vec::new();
+        push_int(&mut bits, self.buffer.len() as u32, 8);
+        push_int(&mut bits, self.seq as u32, 8);
+        for unit in self.buffer.drain(..) {
+            unit.push_bits(&mut bits);
+        }
+        let crc = self.checksum.compute(&bits);
+        push_int(&mut bits, crc, self.checksum.bits());
+        self.seq = self.seq.wrapping_add(1);
+
+        for bit in preamble().into_iter().chain(bits) {
+            self.tx.send(bit)?;
+        }
+        Ok(())
+    }
+}
+
+impl<X, U> Tx for FrameTx<X, U>
+where
+    X: Tx<Item = Signal>,
+    U: FrameUnit,
+{
+    type Item = U;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.buffer.push(value);
+        if self.buffer.len() == self.max_len {
+            self.send_frame()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.send_frame()?;
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        let sent = self.send_frame();
+        let closed = self.tx.close();
+        sent.and(closed)
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Frame Rx ///////////////////
+////////////////////////////////////////////////
+
+/// Yields payload units of frames which pass the checksum. Corrupt and dropped frames are
+/// reported as `FramingError`s, and counted in `stats()`.
+pub struct FrameRx<R, U> {
+    rx: R,
+    checksum: Checksum,
+    /// Last bits seen while hunting for the preamble, and how many of them are there.
+    window: u16,
+    window_len: usize,
+    /// Bits of the current frame after its preamble, or `None` while hunting.
+    frame: Option<Vec<Signal>>,
+    /// Bits to be scanned again after a false preamble.
+    replay: VecDeque<Signal>,
+    expected_seq: Option<u8>,
+    ready: VecDeque<U>,
+    stats: FrameStats,
+}
+
+impl<R, U> FrameRx<R, U>
+where
+    R: Rx<Item = Signal>,
+    U: FrameUnit,
+{
+    pub fn new(rx: R) -> Self {
+        FrameRx {
+            rx,
+            checksum: Checksum::default(),
+            window: 0,
+            window_len: 0,
+            frame: None,
+            replay: VecDeque::new(),
+            expected_seq: None,
+            ready: VecDeque::new(),
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// Set the checksum. Default is `Checksum::Crc16`.
+    pub fn with_checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    fn hunt(&mut self, bit: Signal) {
+        self.window = self.window << 1 | bit as u16;
+        self.window_len = (self.window_len + 1).min(16);
+        if self.window_len == 16 && self.window == PREAMBLE {
+            self.window_len = 0;
+            self.frame = Some(Vec::new());
+        }
+    }
+
+    /// Take the bit into the current frame, and check the frame once it is complete.
+    fn push(&mut self, bit: Signal) -> Result<(), FramingError> {
+        let Some(frame) = &mut self.frame else {
+            self.hunt(bit);
+            return Ok(());
+        };
+        frame.push(bit);
+        if frame.len() < HEADER_BITS {
+            return Ok(());
+        }
+        let len = read_int(&frame[..8]) as usize;
+        let payload_end = HEADER_BITS + len * U::BITS;
+        if frame.len() < payload_end + self.checksum.bits() {
+            return Ok(());
+        }
+
+        let frame = self.frame.take().unwrap();
+        let (checked, crc) = frame.split_at(payload_end);
+        if self.checksum.compute(checked) != read_int(crc) {
+            self.stats.corrupt += 1;
+            // the preamble was false, but a real one may start right after its first bit
+            let replay = preamble().into_iter().skip(1).chain(frame);
+            // bits which have not been scanned yet come after these
+            for bit in replay.rev() {
+                self.replay.push_front(bit);
+            }
+            return Err(FramingError::Corrupt);
+        }
+
+        self.stats.frames += 1;
+        self.ready
+            .extend(checked[HEADER_BITS..].chunks(U::BITS).map(U::from_bits));
+        let seq = read_int(&checked[8..16]) as u8;
+        let dropped = match self.expected_seq {
+            Some(expected) => seq.wrapping_sub(expected) as usize,
+            None => 0,
+        };
+        self.expected_seq = Some(seq.wrapping_add(1));
+        if dropped > 0 {
+            self.stats.dropped += dropped as u64;
+            return Err(FramingError::Dropped(dropped));
+        }
+        Ok(())
+    }
+}
+
+impl<R, U> Rx for FrameRx<R, U>
+where
+    R: Rx<Item = Signal>,
+    U: FrameUnit,
+{
+    type Item = U;
+
+    imp_recv_modes!(self, mode => {
+        loop {
+            if let Some(unit) = self.ready.pop_front() {
+                return Ok(Some(unit));
+            }
+            let bit = match self.replay.pop_front() {
+                Some(bit) => bit,
+                None => match mode.recv(&mut self.rx)? {
+                    Some(bit) => bit,
+                    // incomplete frame is lost
+                    None => return Ok(None),
+                },
+            };
+            self.push(bit).map_err(FlowError::protocol)?;
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transmit<U: FrameUnit>(max_len: usize, payload: Vec<U>) -> Vec<Signal> {
+        let mut bits = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut bits).frame(max_len);
+        tx.send_all(payload).unwrap();
+        tx.close().unwrap();
+        drop(tx);
+        bits
+    }
+
+    /// Collect units, and errors in between.
+    fn receive<U: FrameUnit>(bits: Vec<Signal>) -> (Vec<U>, Vec<FramingError>, FrameStats) {
+        let mut rx = IteratorRx::from(bits).unframe::<U>();
+        let mut units = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match rx.recv() {
+                Ok(Some(unit)) => units.push(unit),
+                Ok(None) => break,
+                Err(e) => errors.push(*e.protocol_error::<FramingError>().unwrap()),
+            }
+        }
+        (units, errors, rx.stats())
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut bits = Vec::new();
+        for byte in b"123456789" {
+            byte.push_bits(&mut bits);
+        }
+        assert_eq!(Checksum::Crc8.compute(&bits), 0xF4);
+        assert_eq!(Checksum::Crc16.compute(&bits), 0x29B1);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let payload = b"Hello, frames!".to_vec();
+        let bits = transmit(4, payload.clone());
+        // 4 frames, the last one incomplete
+        assert_eq!(bits.len(), 4 * (16 + 16 + 16) + payload.len() * 8);
+
+        let (units, errors, stats) = receive::<u8>(bits);
+        assert_eq!(units, payload);
+        assert_eq!(errors, &[]);
+        assert_eq!(stats.frames, 4);
+    }
+
+    #[test]
+    fn test_join_mid_stream() {
+        let payload: Vec<u8> = (0..40).collect();
+        let bits = transmit(10, payload);
+        // start in the middle of the first frame, and lose the third one
+        let frame_bits = bits.len() / 4;
+        let bits: Vec<_> = bits[frame_bits / 2..2 * frame_bits]
+            .iter()
+            .chain(&bits[3 * frame_bits..])
+            .copied()
+            .collect();
+
+        let (units, errors, stats) = receive::<u8>(bits);
+        assert_eq!(units, (10..20).chain(30..40).collect::<Vec<_>>());
+        assert_eq!(errors, &[FramingError::Dropped(1)]);
+        assert_eq!(stats.dropped, 1);
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let payload: Vec<Signal> = (0..60).map(|i| i % 3 == 0).collect();
+        let mut bits = transmit(20, payload.clone());
+        let frame_bits = bits.len() / 3;
+        bits[frame_bits + 40] ^= true;
+
+        let (units, errors, stats) = receive::<Signal>(bits);
+        assert_eq!(units, [&payload[..20], &payload[40..]].concat());
+        assert_eq!(errors, &[FramingError::Corrupt, FramingError::Dropped(1)]);
+        assert_eq!(
+            stats,
+            FrameStats {
+                frames: 2,
+                dropped: 1,
+                corrupt: 1,
+            }
+        );
+    }
+}
@@ -1,14 +1,86 @@
+use std::error::Error;
+use std::fmt;
 use std::marker::PhantomData;
-use std::thread::sleep;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-pub struct Interval<X, R> {
+use crate::cancel::CancellationToken;
+use crate::clock::*;
+use crate::error::FlowError;
+use crate::rx::RecvMode;
+
+pub struct Interval<X, R, C = MonotonicClock> {
     pub(crate) inner: X,
     rate: Duration,
-    last: Option<SystemTime>,
+    clock: C,
+    schedule: Schedule,
+    /// Time of the last call, for `Schedule::Relative`.
+    last: Option<Instant>,
+    /// Time of the next tick, for `Schedule::Fixed`.
+    next: Option<Instant>,
+    lateness: Arc<Mutex<LatenessStats>>,
+    cancellation: Option<CancellationToken>,
     role: PhantomData<R>,
 }
 
+/// When should the next call happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Schedule {
+    /// Next call happens `rate` after the previous one has finished, so a slow call permanently
+    /// shifts the phase of all subsequent ticks.
+    #[default]
+    Relative,
+    /// Calls are anchored at `start + n * rate`, where `start` is the time of the first call.
+    /// What happens when a tick was missed is decided by the policy.
+    Fixed(MissedTickPolicy),
+}
+
+/// What `Schedule::Fixed` interval should do when a call comes in after its tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissedTickPolicy {
+    /// Proceed immediately, and keep proceeding without waiting until the schedule is caught up.
+    Burst,
+    /// Proceed immediately, but drop missed ticks: the next call waits for the next tick in the
+    /// original phase.
+    Skip,
+    /// Like `Skip`, but instead of proceeding, fail with `FlowError::Late` without calling inner
+    /// Tx/Rx.
+    Report,
+}
+
+/// Accumulated statistics about calls which came in after their tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatenessStats {
+    /// Total number of calls which passed through (late or not).
+    pub ticks: u64,
+    /// Number of calls which were late.
+    pub late_ticks: u64,
+    /// Number of whole ticks which passed while waiting for late calls.
+    pub missed_ticks: u64,
+    pub max_lateness: Duration,
+    pub total_lateness: Duration,
+}
+
+/// Clonable read-only access to the `LatenessStats` of an `Interval`, which stays valid after
+/// the interval is moved into a pipeline.
+#[derive(Clone, Debug)]
+pub struct LatenessHandle {
+    stats: Arc<Mutex<LatenessStats>>,
+}
+
+/// Call was late for its tick, and `MissedTickPolicy::Report` was requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LateTickError {
+    pub late_by: Duration,
+    pub missed_ticks: u64,
+}
+
+enum Tick {
+    Now,
+    After(Duration),
+    Late(LateTickError),
+}
+
 pub trait IntervalRole: private::Sealed {
     fn role_name() -> &'static str;
 }
@@ -41,34 +113,83 @@ mod private {
 
 impl<X, R: IntervalRole> Interval<X, R> {
     pub fn new(inner: X, rate: Duration) -> Self {
+        Interval::with_clock(inner, rate, MonotonicClock)
+    }
+}
+
+impl<X, R: IntervalRole, C: Clock> Interval<X, R, C> {
+    pub fn with_clock(inner: X, rate: Duration, clock: C) -> Self {
         Interval {
             inner,
             rate,
+            clock,
+            schedule: Schedule::default(),
             last: None,
+            next: None,
+            lateness: Default::default(),
+            cancellation: None,
             role: Default::default(),
         }
     }
 
-    fn duration_until_next_call(&self, now: SystemTime) -> Option<Duration> {
-        let last = match self.last {
-            Some(time) => time,
-            None => return None,
-        };
+    /// Switch to another schedule, starting over from the next call.
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self.last = None;
+        self.next = None;
+        self
+    }
 
-        let elapsed = match now.duration_since(last) {
-            Ok(elapsed) => elapsed,
-            Err(_e) => {
-                // TODO: log
-                // println!("Interval{}: system time drift error: {:?}", R::role_name(), e);
-                return None;
-            }
+    /// Stop waiting for ticks once `token` is cancelled: a call which is waiting proceeds right
+    /// away, and so do all further calls. This lets a pipeline drain quickly on shutdown;
+    /// use `cancel_on` to stop values from coming in.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+
+    /// Snapshot of lateness statistics gathered so far.
+    pub fn lateness(&self) -> LatenessStats {
+        *self.lateness.lock().unwrap()
+    }
+
+    pub fn lateness_handle(&self) -> LatenessHandle {
+        LatenessHandle {
+            stats: Arc::clone(&self.lateness),
+        }
+    }
+
+    fn record_lateness(&self, late_by: Duration) -> LateTickError {
+        let missed_ticks = if self.rate.is_zero() {
+            0
+        } else {
+            (late_by.as_nanos() / self.rate.as_nanos()) as u64
         };
+        let mut stats = self.lateness.lock().unwrap();
+        stats.late_ticks += 1;
+        stats.missed_ticks += missed_ticks;
+        stats.max_lateness = stats.max_lateness.max(late_by);
+        stats.total_lateness += late_by;
+        LateTickError {
+            late_by,
+            missed_ticks,
+        }
+    }
+
+    fn duration_until_next_call(&self, now: Instant) -> Option<Duration> {
+        let last = self.last?;
+
+        let elapsed = now.saturating_duration_since(last);
 
         let until_next_call = match self.rate.checked_sub(elapsed) {
             Some(until_next_call) => until_next_call,
             None => {
-                // TODO: log
-                // println!("Interval{}: slow receiver, late by {:?}", R::role_name(), (elapsed - self.rate));
+                // slow receiver
+                self.record_lateness(elapsed - self.rate);
                 return None;
             }
         };
@@ -76,15 +197,331 @@ impl<X, R: IntervalRole> Interval<X, R> {
         Some(until_next_call)
     }
 
-    pub(crate) fn sleep_and_update_last_call_time(&mut self) {
-        let mut now = SystemTime::now();
+    /// Decide when the call at `now` may proceed, and move the schedule forward.
+    fn plan_tick(&mut self, now: Instant) -> Tick {
+        let policy = match self.schedule {
+            Schedule::Relative => {
+                return match self.duration_until_next_call(now) {
+                    Some(duration) => Tick::After(duration),
+                    None => Tick::Now,
+                };
+            }
+            Schedule::Fixed(policy) => policy,
+        };
+
+        let next = match self.next {
+            None => {
+                self.next = Some(now + self.rate);
+                return Tick::Now;
+            }
+            Some(next) => next,
+        };
+
+        if now <= next {
+            self.next = Some(next + self.rate);
+            return Tick::After(next - now);
+        }
+
+        let error = self.record_lateness(now - next);
+        // next tick in the original phase which is still ahead of `now`
+        let skip_ticks = u32::try_from(error.missed_ticks + 1).unwrap_or(u32::MAX);
+        let skip_to = next + self.rate * skip_ticks;
+        match policy {
+            MissedTickPolicy::Burst => {
+                self.next = Some(next + self.rate);
+                Tick::Now
+            }
+            MissedTickPolicy::Skip => {
+                self.next = Some(skip_to);
+                Tick::Now
+            }
+            MissedTickPolicy::Report => {
+                self.next = Some(skip_to);
+                Tick::Late(error)
+            }
+        }
+    }
+
+    fn tick_passed(&mut self) {
+        self.last = Some(self.clock.now());
+        self.lateness.lock().unwrap().ticks += 1;
+    }
+
+    /// How long until the next tick, without moving the schedule.
+    fn until_tick(&self, now: Instant) -> Duration {
+        let since_last = |last| now.saturating_duration_since(last);
+        match self.schedule {
+            Schedule::Relative => self.last.map_or(Duration::ZERO, |last| {
+                self.rate.saturating_sub(since_last(last))
+            }),
+            Schedule::Fixed(_) => self
+                .next
+                .map_or(Duration::ZERO, |next| next.saturating_duration_since(now)),
+        }
+    }
+
+    /// Whether a call now would not have to wait for the tick.
+    pub(crate) fn is_tick_due(&self) -> bool {
+        self.until_tick(self.clock.now()).is_zero()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        match &self.cancellation {
+            Some(token) => self.clock.sleep_cancellable(duration, token),
+            None => self.clock.sleep(duration),
+        }
+    }
+
+    /// Block until the next tick according to the schedule.
+    pub(crate) fn wait_for_tick(&mut self) -> Result<(), LateTickError> {
+        match self.plan_tick(self.clock.now()) {
+            Tick::Now => {}
+            Tick::After(duration) => self.sleep(duration),
+            Tick::Late(error) => return Err(error),
+        }
+        self.tick_passed();
+        Ok(())
+    }
+
+    /// Same as `wait_for_tick`, but does not wait past what `mode` allows. Then the tick is
+    /// left for the next call, and `FlowError::Empty` or `FlowError::Timeout` is returned.
+    /// Timeout is counted on the clock of the interval, so that a virtual clock advances by
+    /// exactly that much.
+    pub(crate) fn wait_for_tick_with(&mut self, mode: RecvMode) -> Result<(), FlowError> {
+        let wait = self.until_tick(self.clock.now());
+        match mode {
+            RecvMode::Try if !wait.is_zero() => return Err(FlowError::Empty),
+            RecvMode::Timeout(deadline) => {
+                let timeout = deadline.timeout();
+                if wait > timeout {
+                    self.sleep(timeout);
+                    return Err(FlowError::Timeout);
+                }
+            }
+            _ => {}
+        }
+        Ok(self.wait_for_tick()?)
+    }
 
-        if let Some(duration) = self.duration_until_next_call(now) {
-            sleep(duration);
-            // update current timestamp after sleep
-            now = SystemTime::now();
+    /// Same as `wait_for_tick`, but waits on a timer future instead of blocking the thread.
+    #[cfg(feature = "async")]
+    pub(crate) async fn delay_for_tick(&mut self) -> Result<(), LateTickError> {
+        match self.plan_tick(self.clock.now()) {
+            Tick::Now => {}
+            // a pending timer can't be interrupted, but there is no need to start one
+            Tick::After(_) if self.cancellation.as_ref().is_some_and(|t| t.is_cancelled()) => {}
+            Tick::After(duration) => self.clock.delay(duration).await,
+            Tick::Late(error) => return Err(error),
         }
+        self.tick_passed();
+        Ok(())
+    }
+}
+
+impl LatenessStats {
+    /// Average lateness of late calls.
+    pub fn mean_lateness(&self) -> Duration {
+        if self.late_ticks == 0 {
+            Duration::ZERO
+        } else {
+            self.total_lateness / u32::try_from(self.late_ticks).unwrap_or(u32::MAX)
+        }
+    }
+}
+
+impl LatenessHandle {
+    pub fn snapshot(&self) -> LatenessStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+impl fmt::Display for LateTickError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Interval tick is late by {:?} ({} ticks missed)",
+            self.late_by, self.missed_ticks
+        )
+    }
+}
+
+impl Error for LateTickError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn test_tx_interval_virtual_clock() {
+        let clock = VirtualClock::new();
+        let mut buffer = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut buffer)
+            .interval_with_clock(Duration::from_secs(3600), clock.clone());
+
+        tx.send_all(vec![1, 2, 3]).unwrap();
+        drop(tx);
+
+        assert_eq!(buffer, &[1, 2, 3]);
+        // first call does not wait
+        assert_eq!(clock.elapsed(), Duration::from_secs(2 * 3600));
+    }
+
+    #[test]
+    fn test_tx_interval_try_send() {
+        let clock = VirtualClock::new();
+        let mut buffer = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut buffer)
+            .interval_with_clock(Duration::from_secs(10), clock.clone());
+
+        tx.try_send(1).unwrap();
+        assert!(matches!(tx.try_send(2), Err(FlowError::Full)));
+        clock.advance(Duration::from_secs(10));
+        tx.try_send(3).unwrap();
+        drop(tx);
+
+        assert_eq!(buffer, &[1, 3]);
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_rx_interval_slow_consumer() {
+        let clock = VirtualClock::new();
+        let mut rx = CounterRx::new().interval_with_clock(Duration::from_secs(10), clock.clone());
+
+        assert_eq!(rx.recv().unwrap(), Some(0));
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(rx.recv().unwrap(), Some(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+
+        // consumer was slower than the rate, so no need to wait
+        clock.advance(Duration::from_secs(15));
+        assert_eq!(rx.recv().unwrap(), Some(2));
+        assert_eq!(clock.elapsed(), Duration::from_secs(25));
+    }
+
+    #[test]
+    fn test_rx_interval_try_recv() {
+        let clock = VirtualClock::new();
+        let mut rx = CounterRx::new().interval_with_clock(Duration::from_secs(10), clock.clone());
+
+        assert_eq!(rx.try_recv().unwrap(), Some(0));
+        clock.advance(Duration::from_secs(4));
+        // tick is not due yet, and is not used up by polling
+        assert!(matches!(rx.try_recv(), Err(FlowError::Empty)));
+        assert_eq!(clock.elapsed(), Duration::from_secs(4));
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(rx.try_recv().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_rx_interval_recv_timeout() {
+        let clock = VirtualClock::new();
+        let mut rx = CounterRx::new().interval_with_clock(Duration::from_secs(10), clock.clone());
+
+        assert_eq!(rx.recv().unwrap(), Some(0));
+        let timeout = rx.recv_timeout(Duration::from_secs(4));
+        assert!(matches!(timeout, Err(FlowError::Timeout)));
+        assert_eq!(clock.elapsed(), Duration::from_secs(4));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(60)).unwrap(), Some(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+    }
+
+    fn fixed(
+        policy: MissedTickPolicy,
+        clock: &VirtualClock,
+    ) -> Interval<CounterRx, IntervalRoleRx, VirtualClock> {
+        CounterRx::new()
+            .interval_with_clock(Duration::from_secs(10), clock.clone())
+            .with_schedule(Schedule::Fixed(policy))
+    }
+
+    #[test]
+    fn test_fixed_schedule_keeps_phase() {
+        let clock = VirtualClock::new();
+        let mut rx = fixed(MissedTickPolicy::Skip, &clock);
+
+        rx.recv().unwrap();
+        // slow stage, but still within the tick
+        clock.advance(Duration::from_secs(7));
+        rx.recv().unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(10));
+        clock.advance(Duration::from_secs(3));
+        rx.recv().unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(20));
+        assert_eq!(rx.lateness().late_ticks, 0);
+    }
+
+    #[test]
+    fn test_fixed_schedule_burst() {
+        let clock = VirtualClock::new();
+        let mut rx = fixed(MissedTickPolicy::Burst, &clock);
+
+        rx.recv().unwrap();
+        clock.advance(Duration::from_secs(25));
+        // ticks at 10 and 20 are both late, and pass without waiting
+        rx.recv().unwrap();
+        rx.recv().unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(25));
+        // tick at 30 is on time again
+        rx.recv().unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+
+        let stats = rx.lateness();
+        assert_eq!(stats.ticks, 4);
+        assert_eq!(stats.late_ticks, 2);
+        assert_eq!(stats.missed_ticks, 1);
+        assert_eq!(stats.max_lateness, Duration::from_secs(15));
+        assert_eq!(stats.mean_lateness(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_fixed_schedule_skip() {
+        let clock = VirtualClock::new();
+        let mut rx = fixed(MissedTickPolicy::Skip, &clock);
+        let handle = rx.lateness_handle();
+
+        rx.recv().unwrap();
+        clock.advance(Duration::from_secs(25));
+        rx.recv().unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(25));
+        // tick at 20 is skipped
+        rx.recv().unwrap();
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+        assert_eq!(handle.snapshot().missed_ticks, 1);
+    }
+
+    #[test]
+    fn test_fixed_schedule_report() {
+        let clock = VirtualClock::new();
+        let mut rx = fixed(MissedTickPolicy::Report, &clock);
+
+        assert_eq!(rx.recv().unwrap(), Some(0));
+        clock.advance(Duration::from_secs(12));
+        let late = match rx.recv() {
+            Err(FlowError::Late(late)) => late,
+            other => panic!("expected late tick, got {:?}", other),
+        };
+        assert_eq!(late.late_by, Duration::from_secs(2));
+        // inner Rx was not polled
+        assert_eq!(rx.recv().unwrap(), Some(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_cancel_waiting_tick() {
+        let token = CancellationToken::new();
+        let mut rx = CounterRx::new()
+            .interval(Duration::from_secs(3600))
+            .with_cancellation(token.clone());
+        assert_eq!(rx.recv().unwrap(), Some(0));
 
-        self.last = Some(now);
+        let thread = std::thread::spawn(move || {
+            assert_eq!(rx.recv().unwrap(), Some(1));
+            assert_eq!(rx.recv().unwrap(), Some(2));
+        });
+        std::thread::sleep(Duration::from_millis(10));
+        token.cancel();
+        thread.join().unwrap();
     }
 }
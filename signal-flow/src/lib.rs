@@ -1,10 +1,66 @@
+//! Composable `Tx`/`Rx` pipelines.
+//!
+//! Without the default `std` feature the crate is `no_std` + `alloc`: traits, adapters, error
+//! type and RTSM codec remain, while everything built on threads, clocks or I/O is left out.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+// what `std` prelude would otherwise provide
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+
+#[cfg(feature = "async")]
+pub use crate::asynchronous::*;
+#[cfg(feature = "std")]
+pub use crate::cancel::*;
+#[cfg(feature = "std")]
+pub use crate::clock::*;
+pub use crate::error::*;
+pub use crate::fec::*;
+pub use crate::frame::*;
+#[cfg(feature = "std")]
 pub use crate::interval::*;
+#[cfg(feature = "std")]
+pub use crate::metrics::*;
+#[cfg(feature = "std")]
 pub use crate::pair::*;
+#[cfg(feature = "record")]
+pub use crate::record::*;
+pub use crate::resample::*;
 pub use crate::rx::*;
+#[cfg(feature = "std")]
+pub use crate::simulator::*;
+#[cfg(feature = "socket")]
+pub use crate::socket::*;
+#[cfg(feature = "std")]
+pub use crate::stage::*;
 pub use crate::tx::*;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "std")]
+mod cancel;
+#[cfg(feature = "std")]
+mod clock;
+mod error;
+pub mod fec;
+pub mod frame;
+#[cfg(feature = "std")]
 mod interval;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
 pub mod pair;
+#[cfg(feature = "record")]
+pub mod record;
+pub mod resample;
 pub mod rtsm;
 pub mod rx;
+#[cfg(feature = "std")]
+pub mod simulator;
+#[cfg(feature = "socket")]
+pub mod socket;
+#[cfg(feature = "std")]
+pub mod stage;
 pub mod tx;
@@ -0,0 +1,359 @@
+//! Instrumentation of pipeline stages: item and error counts, inter-arrival times and time
+//! spent blocked in the inner Tx/Rx, collected into a registry which can be snapshotted.
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::*;
+
+/// Accumulated statistics of a single instrumented stage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StageStats {
+    /// Number of values which passed through.
+    pub items: u64,
+    /// Number of failed calls. Non-blocking calls which found nothing to receive do not count.
+    pub errors: u64,
+    /// Whether the inner Rx has ended.
+    pub ended: bool,
+    /// Longest time between two consecutive values.
+    pub max_interval: Duration,
+    /// Total time spent inside the inner Tx/Rx calls.
+    pub total_blocking: Duration,
+    /// Longest single inner Tx/Rx call.
+    pub max_blocking: Duration,
+    first_item: Option<Instant>,
+    last_item: Option<Instant>,
+}
+
+impl StageStats {
+    /// Mean time between two consecutive values.
+    pub fn mean_interval(&self) -> Option<Duration> {
+        match (self.first_item, self.last_item) {
+            (Some(first), Some(last)) if self.items > 1 => {
+                Some((last - first) / u32::try_from(self.items - 1).unwrap_or(u32::MAX))
+            }
+            _ => None,
+        }
+    }
+
+    /// Mean duration of a single inner Tx/Rx call.
+    pub fn mean_blocking(&self) -> Option<Duration> {
+        let calls = self.items + self.errors;
+        if calls == 0 {
+            None
+        } else {
+            Some(self.total_blocking / u32::try_from(calls).unwrap_or(u32::MAX))
+        }
+    }
+
+    /// Values per second between the first and the last value.
+    pub fn throughput(&self) -> Option<f64> {
+        self.mean_interval()
+            .filter(|interval| !interval.is_zero())
+            .map(|interval| 1.0 / interval.as_secs_f64())
+    }
+}
+
+/// Statistics of a named stage as of some point in time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageSnapshot {
+    pub name: String,
+    pub stats: StageStats,
+    /// How long ago the last value passed through. Growing idle time of a stage whose
+    /// upstream is busy means that the stage lags behind.
+    pub idle: Option<Duration>,
+}
+
+type Stages = Vec<(String, Arc<Mutex<StageStats>>)>;
+
+/// Shared collection of stage statistics. Clones refer to the same registry.
+#[derive(Clone, Debug)]
+pub struct MetricsRegistry<C = MonotonicClock> {
+    clock: C,
+    stages: Arc<Mutex<Stages>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        MetricsRegistry::with_clock(MonotonicClock)
+    }
+
+    /// Process-wide registry used by `.instrument(name)` extension methods.
+    pub fn global() -> &'static MetricsRegistry {
+        static GLOBAL: OnceLock<MetricsRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(MetricsRegistry::new)
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock + Clone> MetricsRegistry<C> {
+    pub fn with_clock(clock: C) -> Self {
+        MetricsRegistry {
+            clock,
+            stages: Default::default(),
+        }
+    }
+
+    /// Get recorder of the stage called `name`, creating the stage if needed. Stages registered
+    /// under the same name share their statistics.
+    pub fn register(&self, name: &str) -> StageRecorder<C> {
+        let mut stages = self.stages.lock().unwrap();
+        let stats = match stages.iter().find(|(stage, _)| stage == name) {
+            Some((_, stats)) => Arc::clone(stats),
+            None => {
+                let stats = Arc::new(Mutex::new(StageStats::default()));
+                stages.push((name.to_owned(), Arc::clone(&stats)));
+                stats
+            }
+        };
+        StageRecorder {
+            clock: self.clock.clone(),
+            stats,
+        }
+    }
+
+    /// Statistics of all stages, in the order of registration.
+    pub fn snapshot(&self) -> Vec<StageSnapshot> {
+        let now = self.clock.now();
+        let stages = self.stages.lock().unwrap();
+        stages
+            .iter()
+            .map(|(name, stats)| {
+                let stats = *stats.lock().unwrap();
+                StageSnapshot {
+                    name: name.clone(),
+                    stats,
+                    idle: stats
+                        .last_item
+                        .map(|last| now.saturating_duration_since(last)),
+                }
+            })
+            .collect()
+    }
+
+    /// Statistics of a single stage.
+    pub fn stage(&self, name: &str) -> Option<StageSnapshot> {
+        self.snapshot().into_iter().find(|stage| stage.name == name)
+    }
+}
+
+/// Write access to statistics of a single stage.
+#[derive(Clone, Debug)]
+pub struct StageRecorder<C = MonotonicClock> {
+    clock: C,
+    stats: Arc<Mutex<StageStats>>,
+}
+
+enum Outcome {
+    Item,
+    End,
+    Nothing,
+    Error,
+}
+
+impl<C: Clock> StageRecorder<C> {
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Record a call to the inner Tx/Rx which started at `started` and has just returned.
+    fn record(&self, started: Instant, outcome: Outcome) {
+        let now = self.clock.now();
+        let blocking = now.saturating_duration_since(started);
+        let mut stats = self.stats.lock().unwrap();
+        stats.total_blocking += blocking;
+        stats.max_blocking = stats.max_blocking.max(blocking);
+        match outcome {
+            Outcome::Item => {
+                if let Some(last) = stats.last_item {
+                    let interval = now.saturating_duration_since(last);
+                    stats.max_interval = stats.max_interval.max(interval);
+                }
+                stats.first_item.get_or_insert(now);
+                stats.last_item = Some(now);
+                stats.items += 1;
+            }
+            Outcome::End => stats.ended = true,
+            Outcome::Nothing => {}
+            Outcome::Error => stats.errors += 1,
+        }
+    }
+
+    /// Record outcome of a receive call which started at `started`.
+    pub(crate) fn record_recv<T>(&self, started: Instant, result: &Result<Option<T>, FlowError>) {
+        let outcome = match result {
+            Ok(Some(_)) => Outcome::Item,
+            Ok(None) => Outcome::End,
+            Err(FlowError::Empty | FlowError::Timeout) => Outcome::Nothing,
+            Err(_) => Outcome::Error,
+        };
+        self.record(started, outcome);
+    }
+
+    /// Record outcome of a send call which started at `started`.
+    pub(crate) fn record_send(&self, started: Instant, result: &Result<(), FlowError>) {
+        let outcome = match result {
+            Ok(()) => Outcome::Item,
+            Err(_) => Outcome::Error,
+        };
+        self.record(started, outcome);
+    }
+}
+
+/// Records every value sent through it into a stage of `MetricsRegistry`.
+pub struct InstrumentedTx<X, C = MonotonicClock> {
+    pub(crate) inner: X,
+    pub(crate) recorder: StageRecorder<C>,
+}
+
+impl<X, C> InstrumentedTx<X, C> {
+    pub fn new(inner: X, recorder: StageRecorder<C>) -> Self {
+        InstrumentedTx { inner, recorder }
+    }
+}
+
+impl<X: Tx, C: Clock> Tx for InstrumentedTx<X, C> {
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.send(value);
+        self.recorder.record_send(started, &result);
+        result
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.try_send(value);
+        self.recorder.record_send(started, &result);
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.inner.close()
+    }
+}
+
+/// Records every value received through it into a stage of `MetricsRegistry`.
+pub struct InstrumentedRx<R, C = MonotonicClock> {
+    pub(crate) inner: R,
+    pub(crate) recorder: StageRecorder<C>,
+}
+
+impl<R, C> InstrumentedRx<R, C> {
+    pub fn new(inner: R, recorder: StageRecorder<C>) -> Self {
+        InstrumentedRx { inner, recorder }
+    }
+}
+
+impl<R: Rx, C: Clock> Rx for InstrumentedRx<R, C> {
+    type Item = R::Item;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.recv();
+        self.recorder.record_recv(started, &result);
+        result
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.try_recv();
+        self.recorder.record_recv(started, &result);
+        result
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        let started = self.recorder.now();
+        let result = self.inner.recv_timeout(timeout);
+        self.recorder.record_recv(started, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_instrumented_rx() {
+        let clock = VirtualClock::new();
+        let registry = MetricsRegistry::with_clock(clock.clone());
+        let rx = IteratorRx::from(vec![Ok(1), Err("bad"), Ok(2), Ok(3)])
+            .and_then(|rx: &mut IteratorRx<_>| match rx.recv()? {
+                None => Ok(None),
+                Some(Ok(item)) => Ok(Some(item)),
+                Some(Err(e)) => Err(FlowError::from(e)),
+            })
+            .instrument_in("source", &registry)
+            .interval_with_clock(Duration::from_secs(1), clock.clone())
+            .skip_errors()
+            .instrument_in("output", &registry);
+        assert_eq!(rx.collect_vec().unwrap(), &[1, 2, 3]);
+        clock.advance(Duration::from_secs(5));
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        let source = &snapshot[0];
+        assert_eq!(source.name, "source");
+        assert_eq!(source.stats.items, 3);
+        assert_eq!(source.stats.errors, 1);
+        assert!(source.stats.ended);
+
+        let output = registry.stage("output").unwrap();
+        assert_eq!(output.stats.items, 3);
+        assert_eq!(output.stats.errors, 0);
+        // interval is 1s, but the error took up one tick
+        assert_eq!(output.stats.max_interval, Duration::from_secs(2));
+        assert_eq!(
+            output.stats.mean_interval(),
+            Some(Duration::from_millis(1500))
+        );
+        // the final call waited for one more tick before the end
+        assert_eq!(output.idle, Some(Duration::from_secs(6)));
+        // output stage waits for ticks
+        assert!(output.stats.total_blocking >= Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_instrumented_tx() {
+        let clock = VirtualClock::new();
+        let registry = MetricsRegistry::with_clock(clock.clone());
+        let mut out = Vec::new();
+        {
+            let mut tx = VecCollectorTx::new(&mut out)
+                .interval_with_clock(Duration::from_millis(100), clock.clone())
+                .instrument_in("tick", &registry);
+            tx.send_all(vec![1, 2, 3]).unwrap();
+        }
+        assert_eq!(out, &[1, 2, 3]);
+
+        let tick = registry.stage("tick").unwrap().stats;
+        assert_eq!(tick.items, 3);
+        assert_eq!(tick.throughput(), Some(10.0));
+        assert_eq!(tick.max_blocking, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_shared_stage() {
+        let registry = MetricsRegistry::new();
+        IteratorRx::from(0..2)
+            .instrument_in("numbers", &registry)
+            .collect_vec()
+            .unwrap();
+        IteratorRx::from(0..3)
+            .instrument_in("numbers", &registry)
+            .collect_vec()
+            .unwrap();
+        assert_eq!(registry.snapshot().len(), 1);
+        assert_eq!(registry.stage("numbers").unwrap().stats.items, 5);
+    }
+}
@@ -1,43 +1,391 @@
-//! Tx to Rx adapter built on top of `std::mpsc`.
+//! Tx to Rx adapter for passing values between threads, and its bounded counterpart.
+//!
+//! Both flavours share the same queue, and their receivers may be given a `CancellationToken`
+//! which interrupts blocking `recv()` calls.
+use crate::cancel::*;
+use crate::error::FlowError;
 use crate::rx::*;
 use crate::tx::*;
 
-use std::error::Error;
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
+/// Sending half of the unbounded pair. May be cloned to get multiple producers.
 pub struct SenderTx<T> {
-    sender: Sender<T>,
+    shared: Arc<Shared<T>>,
 }
 
-/// Never returns an error. If corresponding sender hung up,
-/// any further call to `recv()` returns `Ok(None)`.
+/// Returns `FlowError::Cancelled` once cancelled. Otherwise `recv()` never fails, while
+/// `try_recv()` returns `FlowError::Empty` and `recv_timeout()` returns `FlowError::Timeout` if
+/// no value is ready. If corresponding sender hung up, any further call returns `Ok(None)`.
 pub struct ReceiverRx<T> {
-    receiver: Receiver<T>,
+    shared: Arc<Shared<T>>,
+    cancellation: Option<CancellationToken>,
 }
 
 pub fn pair<T>() -> (SenderTx<T>, ReceiverRx<T>) {
-    let (sender, receiver) = channel();
-
-    (SenderTx { sender }, ReceiverRx { receiver })
+    let shared = Shared::new(usize::MAX, OverflowPolicy::Block, VecDeque::new());
+    (
+        SenderTx {
+            shared: Arc::clone(&shared),
+        },
+        ReceiverRx {
+            shared,
+            cancellation: None,
+        },
+    )
 }
 
-impl<T: Send + 'static> Tx for SenderTx<T> {
+impl<T> Tx for SenderTx<T> {
     type Item = T;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
-        self.sender.send(value).map_err(|e| e.into())
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let mut state = self.shared.lock();
+        self.shared.offer(&mut state, value)
+    }
+}
+
+impl<T> Clone for SenderTx<T> {
+    fn clone(&self) -> Self {
+        SenderTx {
+            shared: self.shared.new_sender(),
+        }
+    }
+}
+
+impl<T> Drop for SenderTx<T> {
+    fn drop(&mut self) {
+        self.shared.drop_sender();
+    }
+}
+
+impl<T: Send + 'static> ReceiverRx<T> {
+    /// Make blocked and further calls fail with `FlowError::Cancelled` once `token` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.shared.register(&token);
+        self.cancellation = Some(token);
+        self
     }
 }
 
 impl<T> Rx for ReceiverRx<T> {
     type Item = T;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
-        match self.receiver.recv() {
-            Ok(value) => Ok(Some(value)),
-            Err(_) => Ok(None),
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.shared.recv(self.cancellation.as_ref(), None)
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.shared.try_recv(self.cancellation.as_ref())
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        let deadline = Instant::now() + timeout;
+        self.shared.recv(self.cancellation.as_ref(), Some(deadline))
+    }
+}
+
+impl<T> Drop for ReceiverRx<T> {
+    fn drop(&mut self) {
+        self.shared.drop_receiver();
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Bounded ////////////////////
+////////////////////////////////////////////////
+
+/// What bounded sender should do with a new value when the queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until receiver makes some room.
+    Block,
+    /// Evict the oldest queued value to make room for the new one.
+    DropOldest,
+    /// Discard the new value.
+    DropNewest,
+    /// Fail with `FlowError::Full`, discarding the new value.
+    Error,
+}
+
+/// Sending half of the bounded pair. May be cloned to get multiple producers.
+pub struct BoundedSenderTx<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving half of the bounded pair. Returns `Ok(None)` once the queue is drained and all
+/// senders hung up.
+pub struct BoundedReceiverRx<T> {
+    shared: Arc<Shared<T>>,
+    cancellation: Option<CancellationToken>,
+}
+
+struct Shared<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver: bool,
+    dropped: u64,
+}
+
+/// Create a bounded Tx/Rx pair which holds at most `capacity` values in flight, and applies
+/// `policy` to values sent when it is full.
+pub fn bounded<T>(
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (BoundedSenderTx<T>, BoundedReceiverRx<T>) {
+    assert_ne!(capacity, 0, "Capacity must not be zero");
+    let shared = Shared::new(capacity, policy, VecDeque::with_capacity(capacity));
+    (
+        BoundedSenderTx {
+            shared: Arc::clone(&shared),
+        },
+        BoundedReceiverRx {
+            shared,
+            cancellation: None,
+        },
+    )
+}
+
+impl<T> Shared<T> {
+    fn new(capacity: usize, policy: OverflowPolicy, queue: VecDeque<T>) -> Arc<Self> {
+        Arc::new(Shared {
+            capacity,
+            policy,
+            state: Mutex::new(State {
+                queue,
+                senders: 1,
+                receiver: true,
+                dropped: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+
+    fn push(&self, state: &mut State<T>, value: T) {
+        state.queue.push_back(value);
+        self.not_empty.notify_one();
+    }
+
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let value = state.queue.pop_front();
+        if value.is_some() {
+            self.not_full.notify_one();
+        }
+        value
+    }
+
+    /// Apply non-blocking part of the overflow policy.
+    fn offer(&self, state: &mut State<T>, value: T) -> Result<(), FlowError> {
+        if !state.receiver {
+            return Err(FlowError::Disconnected);
+        }
+        if state.queue.len() < self.capacity {
+            self.push(state, value);
+            return Ok(());
+        }
+        match self.policy {
+            OverflowPolicy::DropOldest => {
+                state.queue.pop_front();
+                state.dropped += 1;
+                self.push(state, value);
+                Ok(())
+            }
+            OverflowPolicy::DropNewest => {
+                state.dropped += 1;
+                Ok(())
+            }
+            OverflowPolicy::Block | OverflowPolicy::Error => Err(FlowError::Full),
         }
     }
+
+    fn new_sender(self: &Arc<Self>) -> Arc<Self> {
+        self.lock().senders += 1;
+        Arc::clone(self)
+    }
+
+    fn drop_sender(&self) {
+        let mut state = self.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.not_empty.notify_all();
+        }
+    }
+
+    fn drop_receiver(&self) {
+        let mut state = self.lock();
+        state.receiver = false;
+        state.queue.clear();
+        self.not_full.notify_all();
+    }
+
+    /// Wait for a value until the optional `deadline`, unless `cancellation` is cancelled.
+    fn recv(
+        &self,
+        cancellation: Option<&CancellationToken>,
+        deadline: Option<Instant>,
+    ) -> Result<Option<T>, FlowError> {
+        let mut state = self.lock();
+        loop {
+            // checked under the lock, so that cancellation can't slip in before the wait
+            if cancellation.is_some_and(CancellationToken::is_cancelled) {
+                return Err(FlowError::Cancelled);
+            }
+            if let Some(value) = self.pop(&mut state) {
+                return Ok(Some(value));
+            }
+            if state.senders == 0 {
+                return Ok(None);
+            }
+            state = match deadline {
+                None => self.not_empty.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(FlowError::Timeout);
+                    }
+                    self.not_empty
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+            };
+        }
+    }
+
+    fn try_recv(&self, cancellation: Option<&CancellationToken>) -> Result<Option<T>, FlowError> {
+        if cancellation.is_some_and(CancellationToken::is_cancelled) {
+            return Err(FlowError::Cancelled);
+        }
+        let mut state = self.lock();
+        match self.pop(&mut state) {
+            Some(value) => Ok(Some(value)),
+            None if state.senders == 0 => Ok(None),
+            None => Err(FlowError::Empty),
+        }
+    }
+}
+
+impl<T: Send + 'static> Shared<T> {
+    fn register(self: &Arc<Self>, token: &CancellationToken) {
+        let shared: Arc<dyn Wake> = Arc::clone(self) as _;
+        token.register(Arc::downgrade(&shared));
+    }
+}
+
+impl<T: Send> Wake for Shared<T> {
+    fn wake(&self) {
+        // taking the lock makes sure that the receiver is either waiting, or yet to check
+        let _state = self.lock();
+        self.not_empty.notify_all();
+    }
+}
+
+impl<T> BoundedSenderTx<T> {
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Number of values discarded by `DropOldest` or `DropNewest` policy so far.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+}
+
+impl<T> Tx for BoundedSenderTx<T> {
+    type Item = T;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let mut state = self.shared.lock();
+        if self.shared.policy == OverflowPolicy::Block {
+            while state.receiver && state.queue.len() >= self.shared.capacity {
+                state = self.shared.not_full.wait(state).unwrap();
+            }
+        }
+        self.shared.offer(&mut state, value)
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let mut state = self.shared.lock();
+        self.shared.offer(&mut state, value)
+    }
+}
+
+impl<T> Clone for BoundedSenderTx<T> {
+    fn clone(&self) -> Self {
+        BoundedSenderTx {
+            shared: self.shared.new_sender(),
+        }
+    }
+}
+
+impl<T> Drop for BoundedSenderTx<T> {
+    fn drop(&mut self) {
+        self.shared.drop_sender();
+    }
+}
+
+impl<T> BoundedReceiverRx<T> {
+    /// Number of values currently queued.
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of values discarded by `DropOldest` or `DropNewest` policy so far.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+}
+
+impl<T: Send + 'static> BoundedReceiverRx<T> {
+    /// Make blocked and further calls fail with `FlowError::Cancelled` once `token` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.shared.register(&token);
+        self.cancellation = Some(token);
+        self
+    }
+}
+
+impl<T> Rx for BoundedReceiverRx<T> {
+    type Item = T;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.shared.recv(self.cancellation.as_ref(), None)
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.shared.try_recv(self.cancellation.as_ref())
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        let deadline = Instant::now() + timeout;
+        self.shared.recv(self.cancellation.as_ref(), Some(deadline))
+    }
+}
+
+impl<T> Drop for BoundedReceiverRx<T> {
+    fn drop(&mut self) {
+        self.shared.drop_receiver();
+    }
 }
 
 #[cfg(test)]
@@ -59,4 +407,106 @@ mod test {
         assert!(matches!(vec, Ok(_)));
         assert_eq!(vec.unwrap(), &[42, 37]);
     }
+
+    #[test]
+    fn test_mpsc_try_recv() {
+        let (mut sender, mut receiver) = pair();
+
+        let err = receiver.try_recv().unwrap_err();
+        assert!(matches!(err, FlowError::Empty));
+        let err = receiver.recv_timeout(Duration::from_millis(1)).unwrap_err();
+        assert!(matches!(err, FlowError::Timeout));
+
+        sender.send(12).unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), Some(12));
+        drop(sender);
+        assert_eq!(receiver.try_recv().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bounded_drop_oldest() {
+        let (mut sender, receiver) = bounded(2, OverflowPolicy::DropOldest);
+
+        sender.send_all(vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(sender.dropped(), 2);
+        drop(sender);
+
+        assert_eq!(receiver.collect_vec().unwrap(), &[3, 4]);
+    }
+
+    #[test]
+    fn test_bounded_drop_newest() {
+        let (mut sender, receiver) = bounded(2, OverflowPolicy::DropNewest);
+
+        sender.send_all(vec![1, 2, 3, 4]).unwrap();
+        drop(sender);
+
+        assert_eq!(receiver.dropped(), 2);
+        assert_eq!(receiver.collect_vec().unwrap(), &[1, 2]);
+    }
+
+    #[test]
+    fn test_bounded_error() {
+        let (mut sender, mut receiver) = bounded(1, OverflowPolicy::Error);
+
+        sender.send(1).unwrap();
+        let err = sender.send(2).unwrap_err();
+        assert!(matches!(err, FlowError::Full));
+
+        assert_eq!(receiver.try_recv().unwrap(), Some(1));
+        assert!(matches!(receiver.try_recv(), Err(FlowError::Empty)));
+        drop(receiver);
+
+        let err = sender.send(3).unwrap_err();
+        assert!(err.is_disconnected());
+    }
+
+    #[test]
+    fn test_bounded_block() {
+        let (mut sender, mut receiver) = bounded(1, OverflowPolicy::Block);
+
+        sender.send(1).unwrap();
+        assert!(matches!(sender.try_send(2), Err(FlowError::Full)));
+
+        let thread = std::thread::spawn(move || {
+            // blocks until the receiver makes room
+            sender.send_all(vec![2, 3]).unwrap();
+        });
+        assert_eq!(receiver.recv().unwrap(), Some(1));
+        assert_eq!(receiver.recv().unwrap(), Some(2));
+        assert_eq!(receiver.recv().unwrap(), Some(3));
+        thread.join().unwrap();
+        assert_eq!(receiver.recv().unwrap(), None);
+    }
+
+    #[test]
+    fn test_bounded_recv_timeout() {
+        let (sender, mut receiver) = bounded::<i32>(1, OverflowPolicy::Block);
+
+        let err = receiver.recv_timeout(Duration::from_millis(1)).unwrap_err();
+        assert!(matches!(err, FlowError::Timeout));
+        drop(sender);
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(1)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cancel_blocked_recv() {
+        let token = CancellationToken::new();
+        let (sender, receiver) = pair::<i32>();
+        let mut receiver = receiver.with_cancellation(token.child_token());
+
+        let thread = std::thread::spawn(move || receiver.recv());
+        token.cancel();
+        assert!(thread.join().unwrap().unwrap_err().is_cancelled());
+        drop(sender);
+
+        let (mut sender, receiver) = bounded(1, OverflowPolicy::Block);
+        let mut receiver = receiver.with_cancellation(token);
+        assert!(receiver.try_recv().unwrap_err().is_cancelled());
+        // the queue stays intact for senders
+        sender.send(1).unwrap();
+    }
 }
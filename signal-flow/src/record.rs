@@ -0,0 +1,301 @@
+//! Recording of received values with their timing to a compact file, and replaying them back.
+//!
+//! File starts with a header made of `MAGIC` and little-endian `u16` format version, followed
+//! by bincode-encoded `(offset in microseconds, value)` records until the end of file.
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::*;
+
+pub const MAGIC: &[u8; 4] = b"SFRC";
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Recording could not be replayed because it is not a recording at all, was made by an
+/// incompatible version, or was cut short.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// File ends in the middle of a record.
+    Truncated,
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::BadMagic => "not a signal-flow recording".fmt(f),
+            RecordingError::UnsupportedVersion(version) => {
+                write!(f, "unsupported recording format version {}", version)
+            }
+            RecordingError::Truncated => "recording ends in the middle of a record".fmt(f),
+        }
+    }
+}
+
+impl Error for RecordingError {}
+
+impl From<RecordingError> for FlowError {
+    fn from(e: RecordingError) -> Self {
+        FlowError::protocol(e)
+    }
+}
+
+fn flow_error(e: bincode::ErrorKind) -> FlowError {
+    match e {
+        bincode::ErrorKind::Io(e) => FlowError::Io(e),
+        other => FlowError::protocol(other),
+    }
+}
+
+/// Passes values of the inner Rx through, writing each of them along with the time passed
+/// since the recording has started. Errors are passed through but not recorded.
+pub struct RecordingRx<R, W, C = MonotonicClock> {
+    inner: R,
+    writer: W,
+    clock: C,
+    start: Instant,
+}
+
+impl<R> RecordingRx<R, BufWriter<File>> {
+    /// Record into a new file at `path`, truncating it if it exists.
+    pub fn create<P: AsRef<Path>>(inner: R, path: P) -> Result<Self, FlowError> {
+        let file = File::create(path)?;
+        RecordingRx::new(inner, BufWriter::new(file))
+    }
+}
+
+impl<R, W: Write> RecordingRx<R, W> {
+    pub fn new(inner: R, writer: W) -> Result<Self, FlowError> {
+        RecordingRx::with_clock(inner, writer, MonotonicClock)
+    }
+}
+
+impl<R, W: Write, C: Clock> RecordingRx<R, W, C> {
+    /// Start recording right away, measuring time with the given `clock`.
+    pub fn with_clock(inner: R, mut writer: W, clock: C) -> Result<Self, FlowError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        let start = clock.now();
+        Ok(RecordingRx {
+            inner,
+            writer,
+            clock,
+            start,
+        })
+    }
+
+    /// Flush and give the writer back.
+    pub fn finish(mut self) -> Result<W, FlowError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<R, W, C> Rx for RecordingRx<R, W, C>
+where
+    R: Rx,
+    R::Item: Serialize,
+    W: Write,
+    C: Clock,
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        match mode.recv(&mut self.inner)? {
+            Some(item) => {
+                let offset = self.clock.now().saturating_duration_since(self.start);
+                let micros = u64::try_from(offset.as_micros()).unwrap_or(u64::MAX);
+                bincode::serialize_into(&mut self.writer, &(micros, &item))
+                    .map_err(|e| flow_error(*e))?;
+                Ok(Some(item))
+            }
+            None => {
+                self.writer.flush()?;
+                Ok(None)
+            }
+        }
+    });
+}
+
+/// How fast `ReplayRx` yields recorded values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pacing {
+    /// Yield values as fast as possible.
+    Unpaced,
+    /// Wait before each value until the same time has passed since the start of the replay as
+    /// it had passed since the start of the recording.
+    Original,
+}
+
+/// Yields values recorded by `RecordingRx`.
+pub struct ReplayRx<T, Rd, C = MonotonicClock> {
+    reader: Rd,
+    pacing: Pacing,
+    clock: C,
+    start: Option<Instant>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ReplayRx<T, BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P, pacing: Pacing) -> Result<Self, FlowError> {
+        let file = File::open(path)?;
+        ReplayRx::new(BufReader::new(file), pacing)
+    }
+}
+
+impl<T, Rd: Read> ReplayRx<T, Rd> {
+    pub fn new(reader: Rd, pacing: Pacing) -> Result<Self, FlowError> {
+        ReplayRx::with_clock(reader, pacing, MonotonicClock)
+    }
+}
+
+impl<T, Rd: Read, C: Clock> ReplayRx<T, Rd, C> {
+    /// Check the header right away. Replay starts with the first `recv()`.
+    pub fn with_clock(mut reader: Rd, pacing: Pacing, clock: C) -> Result<Self, FlowError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(RecordingError::BadMagic.into());
+        }
+        let mut version = [0; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(RecordingError::UnsupportedVersion(version).into());
+        }
+        Ok(ReplayRx {
+            reader,
+            pacing,
+            clock,
+            start: None,
+            _marker: Default::default(),
+        })
+    }
+}
+
+impl<T, Rd, C> Rx for ReplayRx<T, Rd, C>
+where
+    T: DeserializeOwned,
+    Rd: Read,
+    C: Clock,
+{
+    type Item = T;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        let start = *self.start.get_or_insert_with(|| self.clock.now());
+        // end of file is only clean before the first byte of a record
+        let mut first = [0; 1];
+        loop {
+            match self.reader.read(&mut first) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let reader = (&first[..]).chain(&mut self.reader);
+        let (micros, item): (u64, T) = match bincode::deserialize_from(reader) {
+            Ok(record) => record,
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(ref io) if io.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(RecordingError::Truncated.into());
+                }
+                e => return Err(flow_error(e)),
+            },
+        };
+        if self.pacing == Pacing::Original {
+            let due = start + Duration::from_micros(micros);
+            let now = self.clock.now();
+            if due > now {
+                self.clock.sleep(due - now);
+            }
+        }
+        Ok(Some(item))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtsm::*;
+
+    fn record<T: Serialize + Clone>(values: &[T], clock: &VirtualClock) -> Vec<u8> {
+        let rx = IteratorRx::from(values.to_vec())
+            .interval_with_clock(Duration::from_millis(100), clock.clone());
+        let mut recording = RecordingRx::with_clock(rx, Vec::new(), clock.clone()).unwrap();
+        while recording.recv().unwrap().is_some() {}
+        recording.finish().unwrap()
+    }
+
+    #[test]
+    fn test_replay_into_rtsm() {
+        let clock = VirtualClock::new();
+        let bytes = record(&[50u32, 0, 50, 0, 1, 50, 51], &clock);
+
+        let replay = ReplayRx::<u32, _>::new(&bytes[..], Pacing::Unpaced).unwrap();
+        let signal = replay
+            .rtsm(RtsmRanges::new(0..3, 50..52).unwrap())
+            .collect_vec()
+            .unwrap();
+        assert_eq!(signal, &[ON, OFF, ON, OFF, OFF, ON, ON]);
+    }
+
+    #[test]
+    fn test_replay_original_pacing() {
+        let clock = VirtualClock::new();
+        let bytes = record(&['a', 'b', 'c'], &clock);
+
+        let clock = VirtualClock::new();
+        let replay =
+            ReplayRx::<char, _, _>::with_clock(&bytes[..], Pacing::Original, clock.clone())
+                .unwrap();
+        assert_eq!(replay.collect_vec().unwrap(), &['a', 'b', 'c']);
+        // first value was received right away, then one per 100ms tick
+        assert_eq!(clock.elapsed(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_replay_truncated() {
+        let bytes = record(&[1u32, 2, 3], &VirtualClock::new());
+        let mut replay =
+            ReplayRx::<u32, _>::new(&bytes[..bytes.len() - 1], Pacing::Unpaced).unwrap();
+        assert_eq!(replay.recv().unwrap(), Some(1));
+        assert_eq!(replay.recv().unwrap(), Some(2));
+        let err = replay.recv().unwrap_err();
+        assert_eq!(
+            err.protocol_error::<RecordingError>(),
+            Some(&RecordingError::Truncated)
+        );
+
+        // cut between records
+        let record_len = (bytes.len() - 6) / 3;
+        let replay =
+            ReplayRx::<u32, _>::new(&bytes[..bytes.len() - record_len], Pacing::Unpaced).unwrap();
+        assert_eq!(replay.collect_vec().unwrap(), &[1, 2]);
+    }
+
+    #[test]
+    fn test_replay_bad_header() {
+        let err = ReplayRx::<u32, _>::new(&b"JUNKJUNK"[..], Pacing::Unpaced).err();
+        assert_eq!(
+            err.unwrap().protocol_error::<RecordingError>(),
+            Some(&RecordingError::BadMagic)
+        );
+
+        let mut bytes = record(&[1u32], &VirtualClock::new());
+        bytes[4] = 2;
+        let err = ReplayRx::<u32, _>::new(&bytes[..], Pacing::Unpaced).err();
+        assert_eq!(
+            err.unwrap().protocol_error::<RecordingError>(),
+            Some(&RecordingError::UnsupportedVersion(2))
+        );
+    }
+}
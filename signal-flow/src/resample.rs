@@ -0,0 +1,313 @@
+//! Rate conversion of timestamped value streams.
+//!
+//! A consumer which polls a provider faster than the provider updates its value sees every value
+//! several times, and occasionally a damaged or mistimed one near transitions. `ResampleRx` slices
+//! such a stream into periods of the provider's rate and reduces each period to a single value.
+use alloc::collections::VecDeque;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use super::*;
+
+/// Stamps received values with the time elapsed since the first one.
+#[cfg(feature = "std")]
+pub struct TimestampRx<R, C = MonotonicClock> {
+    inner: R,
+    clock: C,
+    start: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Rx> TimestampRx<R> {
+    pub fn new(inner: R) -> Self {
+        TimestampRx::with_clock(inner, MonotonicClock)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Rx, C: Clock> TimestampRx<R, C> {
+    pub fn with_clock(inner: R, clock: C) -> Self {
+        TimestampRx {
+            inner,
+            clock,
+            start: None,
+        }
+    }
+
+    fn stamp(&mut self, value: R::Item) -> (Duration, R::Item) {
+        let now = self.clock.now();
+        let start = *self.start.get_or_insert(now);
+        (now - start, value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Rx, C: Clock> Rx for TimestampRx<R, C> {
+    type Item = (Duration, R::Item);
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.inner.recv()?.map(|value| self.stamp(value)))
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.inner.try_recv()?.map(|value| self.stamp(value)))
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self
+            .inner
+            .recv_timeout(timeout)?
+            .map(|value| self.stamp(value)))
+    }
+}
+
+/// How `ResampleRx` reduces input values of each output period.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResampleMode {
+    /// Yield the latest input value at the end of every period, repeating it through periods
+    /// without input.
+    HoldLast,
+    /// Yield the first input value of every period, and nothing for periods without input.
+    Decimate,
+    /// Yield the most frequent input value of every period, and nothing for periods without
+    /// input. Ties go to the value seen last.
+    ///
+    /// Periods are aligned to the transitions of input values, so that they follow the phase
+    /// and drift of the provider. A new value has to be seen twice to make a transition, so
+    /// single damaged samples are only outvoted. Periods shorter than half at the start and the
+    /// end of input yield nothing. Meant for input oversampled 3 to 5 times.
+    MajorityVote,
+}
+
+/// Converts a stream of `(timestamp, value)` pairs into a stream of values, one per `period`.
+/// Timestamps must not decrease.
+pub struct ResampleRx<R, T> {
+    inner: R,
+    period: Duration,
+    mode: ResampleMode,
+    /// Start of the current period, known after the first input value.
+    start: Option<Duration>,
+    /// Input values of the current period, with their timestamps.
+    votes: Vec<(Duration, T)>,
+    /// Latest input value, and its timestamp. Glitches do not count in `MajorityVote` mode.
+    last: Option<(Duration, T)>,
+    /// Values seen once since `last`, with their first timestamps. Seen again, a value is a
+    /// transition; otherwise, it is a glitch.
+    candidates: Vec<(Duration, T)>,
+    /// Reduced values of past periods which were not received yet.
+    ready: VecDeque<T>,
+    ended: bool,
+}
+
+impl<R, T> ResampleRx<R, T>
+where
+    R: Rx<Item = (Duration, T)>,
+    T: Clone + PartialEq,
+{
+    pub fn new(inner: R, period: Duration, mode: ResampleMode) -> Self {
+        assert!(!period.is_zero(), "Period must not be zero");
+        ResampleRx {
+            inner,
+            period,
+            mode,
+            start: None,
+            votes: Vec::new(),
+            last: None,
+            candidates: Vec::new(),
+            ready: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    /// Reduce the current period, and start the next one at `next`.
+    fn close_period(&mut self, next: Duration) {
+        let votes = core::mem::take(&mut self.votes)
+            .into_iter()
+            .map(|(_, value)| value);
+        let value = match self.mode {
+            ResampleMode::HoldLast => self.last.as_ref().map(|(_, value)| value.clone()),
+            ResampleMode::Decimate => votes.into_iter().next(),
+            ResampleMode::MajorityVote => majority(votes.collect()),
+        };
+        self.ready.extend(value);
+        self.start = Some(next);
+    }
+
+    fn push(&mut self, time: Duration, value: T) {
+        let mut start = *self.start.get_or_insert(time);
+        while time >= start + self.period {
+            self.close_period(start + self.period);
+            start += self.period;
+        }
+        self.votes.push((time, value.clone()));
+        if self.mode != ResampleMode::MajorityVote {
+            self.last = Some((time, value));
+            return;
+        }
+        if let Some((last_time, last)) = &self.last
+            && *last != value
+        {
+            let Some(&(first, _)) = self.candidates.iter().find(|(_, c)| *c == value) else {
+                self.candidates.push((time, value));
+                return;
+            };
+            self.align((*last_time + first) / 2);
+        }
+        self.candidates.clear();
+        self.last = Some((time, value));
+    }
+
+    /// Whether the period cut short by the end of input yields a value. In `MajorityVote` mode it
+    /// has to last half a period, like the first one.
+    fn last_period_counts(&self) -> bool {
+        match (self.votes.last(), self.start) {
+            (Some((time, _)), Some(start)) if self.mode == ResampleMode::MajorityVote => {
+                *time >= start + self.period / 2
+            }
+            (last, _) => last.is_some(),
+        }
+    }
+
+    /// Start a new period at `transition` which happened in the past, and move votes after it
+    /// there.
+    fn align(&mut self, transition: Duration) {
+        let start = self.start.unwrap_or(transition);
+        let split = self.votes.partition_point(|(time, _)| *time < transition);
+        let after = self.votes.split_off(split);
+        if transition >= start + self.period / 2 {
+            self.close_period(transition);
+        } else {
+            // so far the period saw only the tail of the previous value, which has been
+            // accounted for already
+            self.start = Some(transition);
+        }
+        self.votes = after;
+    }
+}
+
+fn majority<T: PartialEq>(mut votes: Vec<T>) -> Option<T> {
+    let mut best = None;
+    let mut best_count = 0;
+    for (i, vote) in votes.iter().enumerate() {
+        let count = votes.iter().filter(|other| *other == vote).count();
+        if count >= best_count {
+            best = Some(i);
+            best_count = count;
+        }
+    }
+    best.map(|i| votes.swap_remove(i))
+}
+
+impl<R, T> Rx for ResampleRx<R, T>
+where
+    R: Rx<Item = (Duration, T)>,
+    T: Clone + PartialEq,
+{
+    type Item = T;
+
+    imp_recv_modes!(self, mode => {
+        loop {
+            if let Some(value) = self.ready.pop_front() {
+                return Ok(Some(value));
+            }
+            if self.ended {
+                return Ok(None);
+            }
+            match mode.recv(&mut self.inner)? {
+                Some((time, value)) => self.push(time, value),
+                None => {
+                    self.ended = true;
+                    if self.last_period_counts() {
+                        self.close_period(Duration::MAX);
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_millis(100);
+
+    fn stamped(samples: &[(u64, u32)]) -> IteratorRx<alloc::vec::IntoIter<(Duration, u32)>> {
+        let samples: Vec<_> = samples
+            .iter()
+            .map(|&(millis, value)| (Duration::from_millis(millis), value))
+            .collect();
+        IteratorRx::from(samples)
+    }
+
+    #[test]
+    fn test_hold_last() {
+        let rx = stamped(&[(0, 1), (40, 2), (250, 3)]).resample(PERIOD, ResampleMode::HoldLast);
+        assert_eq!(rx.collect_vec().unwrap(), &[2, 2, 3]);
+    }
+
+    #[test]
+    fn test_decimate() {
+        let rx = stamped(&[(0, 1), (40, 2), (120, 3), (350, 4), (380, 5)])
+            .resample(PERIOD, ResampleMode::Decimate);
+        assert_eq!(rx.collect_vec().unwrap(), &[1, 3, 4]);
+    }
+
+    #[test]
+    fn test_majority_vote() {
+        // provider changes values at 30, 130 and 230, with a glitch in the middle
+        let rx = stamped(&[
+            (0, 1),
+            (25, 1),
+            (50, 2),
+            (75, 2),
+            (100, 9),
+            (125, 2),
+            (150, 3),
+            (175, 3),
+            (200, 3),
+            (225, 3),
+            (250, 4),
+            (275, 4),
+            (300, 4),
+        ])
+        .resample(PERIOD, ResampleMode::MajorityVote);
+        assert_eq!(rx.collect_vec().unwrap(), &[2, 3, 4]);
+    }
+
+    #[cfg(feature = "std")]
+    fn oversample(conditions: ChannelConditions, seed: u64) -> Vec<u32> {
+        let clock = VirtualClock::new();
+        let provider = IteratorRx::from(0..200);
+        ChannelSimulatorRx::with_clock(provider, PERIOD, conditions, seed, clock.clone())
+            .timestamp_with(clock)
+            .resample(PERIOD, ResampleMode::MajorityVote)
+            .collect_vec()
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_majority_vote_oversampled_channel() {
+        let mut conditions = ChannelConditions {
+            skew: -0.75,
+            ..ChannelConditions::performance_monitor()
+        };
+        for seed in 0..10 {
+            let values = oversample(conditions, seed);
+            // sampling starts in the middle of the first value, which may be too short to count
+            let first = values[0];
+            assert!(first <= 1, "seed {}", seed);
+            assert_eq!(values, (first..200).collect::<Vec<_>>(), "seed {}", seed);
+        }
+
+        // a glitch may happen to look like a neighbour, but most are outvoted
+        conditions.corrupt_rate = 0.05;
+        let expected: Vec<_> = (0..200).collect();
+        for seed in 0..10 {
+            let rate = error_rate(&expected, &oversample(conditions, seed));
+            assert!(rate <= 0.02, "seed {}: error rate {}", seed, rate);
+        }
+    }
+}
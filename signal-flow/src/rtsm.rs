@@ -1,12 +1,16 @@
 use crate::*;
 
-use std::ops::{Range, Sub};
+use alloc::collections::VecDeque;
+use core::ops::{Add, Range, Sub};
 
 pub type Signal = bool;
 
 pub const ON: Signal = true;
 pub const OFF: Signal = false;
 
+/// Index of a level in `RtsmLevels`, i.e. a letter of its alphabet.
+pub type Symbol = usize;
+
 #[derive(Clone)]
 pub struct RtsmRanges<T> {
     off: Range<T>,
@@ -22,6 +26,47 @@ pub const RANGE_100_QUARTER: RtsmRanges<u32> = RtsmRanges {
     on: 75..100,
 };
 
+/// Generalization of `RtsmRanges` to any number of levels: a value within `i`-th range carries
+/// symbol `i`. Binary `RtsmRanges` are the levels `[off, on]`.
+///
+/// # Capacity
+///
+/// Every sample carries `log2(levels)` bits, e.g. 2 bits for 4 levels and 4 bits for 16 levels.
+/// Each range must hold at least 2 values so that a repeated symbol still changes the value, so a
+/// value space of size `V` fits at most `V / 2` levels. Wider ranges survive more lost samples,
+/// as the receiver could tell how far a value has moved within its range.
+#[derive(Clone, Debug)]
+pub struct RtsmLevels<T> {
+    ranges: Vec<Range<T>>,
+}
+
+/// Ranges which are empty, too narrow to repeat a symbol, or overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidRangesError;
+
+/// Symbol outside of the alphabet of `RtsmLevels` was given to an encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSymbolError(pub Symbol);
+
+/// Partitioning of a value space into `RtsmLevels` for several lanes, e.g. instances of a counter.
+///
+/// The space is split evenly into `lanes` blocks, and each block into `levels` ranges, so that no
+/// two ranges of the plan overlap. Lanes beyond the count reuse the blocks cyclically.
+///
+/// Both ends of a channel should derive their ranges from the same plan. Its text form is
+/// `start..end/LANESxLEVELS`, e.g. `10..90/4x2`, which is also how it is (de)serialized with the
+/// `serde` feature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangePlan<T> {
+    space: Range<T>,
+    lanes: usize,
+    levels: usize,
+}
+
+/// Text which is not a valid `RangePlan`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseRangePlanError;
+
 #[derive(Clone)]
 struct RangeValue<T> {
     range: Range<T>,
@@ -35,6 +80,20 @@ pub trait SignalValue: Clone + Eq + PartialOrd<Self> + Sub<Output = Self> {
 
     /// `self + 1` wrapped around the bounds of the given range.
     fn wrapping_next(&self, range: &Range<Self>) -> Self;
+
+    /// How many `wrapping_next` steps lead from `self` to `to` within the given range.
+    ///
+    /// Default implementation takes the steps one by one. `to` must be within the range.
+    fn wrapping_distance(&self, to: &Self, range: &Range<Self>) -> usize {
+        assert!(range.contains(to), "Value must be within the range");
+        let mut value = self.clone();
+        let mut distance = 0;
+        while value != *to {
+            value = value.wrapping_next(range);
+            distance += 1;
+        }
+        distance
+    }
 }
 
 /// RTSM-proto (Ratijas Slow-Mode Protocol) transmitter.
@@ -43,27 +102,124 @@ pub trait SignalValue: Clone + Eq + PartialOrd<Self> + Sub<Output = Self> {
 /// `T` down the pipeline.
 pub struct RtsmTx<X: Tx> {
     tx: X,
-    off: RangeValue<X::Item>,
-    on: RangeValue<X::Item>,
-    current: Option<Signal>,
+    rtsm: RtsmEncoder<X::Item>,
+}
+
+/// Synchronous RTSM encoder of signals or symbols into values, for providers which write values
+/// themselves rather than sending them down a pipeline.
+pub struct RtsmEncoder<T> {
+    levels: Vec<RangeValue<T>>,
+    current: Option<Symbol>,
 }
 
 struct RtsmRxCore<T> {
-    off: Range<T>,
-    on: Range<T>,
-    last: Option<T>,
+    levels: Vec<Range<T>>,
+    /// Last value of every symbol, because encoder carries on from it when the symbol comes back.
+    values: Vec<Option<T>>,
+    last: Option<Symbol>,
 }
 
 /// RTSM-proto (Ratijas Slow-Mode Protocol) receiver.
+///
+/// Values of a signal which lasts for several ticks increment within its range, so when the
+/// consumer misses some ticks, the gap between consecutive values tells how many. Such lost
+/// samples are counted, and may be reconstructed with `LostSamplePolicy::Reconstruct`.
 pub struct RtsmRx<X: Rx> {
     rx: X,
     rtsm: RtsmRxCore<X::Item>,
+    policy: LostSamplePolicy,
+    lost: u64,
+    /// Reconstructed signal, and how many more times to yield it.
+    repeat: Option<(Signal, usize)>,
+}
+
+/// What `RtsmRx` should do with samples it has missed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LostSamplePolicy {
+    /// Only count them, and yield each signal once.
+    Ignore,
+    /// Yield the signal once for every tick since the last value. A gap longer than half of the
+    /// range could as well have wrapped around it, and fails with `AmbiguousGapError`. The
+    /// signal after the gap is then yielded once, by the next call.
+    Reconstruct,
+}
+
+/// Gap between two values of a signal is too long to tell how many samples have been lost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AmbiguousGapError {
+    /// Ticks between the values, assuming the value has not wrapped around the range.
+    pub ticks: usize,
+    pub range_len: usize,
+}
+
+/// Multi-level RTSM transmitter. Give it symbols, and it will send values of `RtsmLevels`.
+pub struct RtsmSymbolTx<X: Tx> {
+    tx: X,
+    rtsm: RtsmEncoder<X::Item>,
+}
+
+/// Multi-level RTSM receiver. Yields a symbol of `RtsmLevels` for every new value.
+pub struct RtsmSymbolRx<X: Rx> {
+    rx: X,
+    rtsm: RtsmRxCore<X::Item>,
+}
+
+/// RTSM transmitter of several lanes at once, each with its own ranges. Lane `i` gets ranges
+/// from the factory called with `i` when its first signal is sent.
+pub struct RtsmMultiTx<X, T, F> {
+    tx: X,
+    factory: F,
+    rtsm: Vec<RtsmEncoder<T>>,
 }
 
+/// RTSM receiver of several lanes at once, each with its own ranges. Yields a signal of every
+/// lane at once.
+///
+/// By default all lanes must change on the same sample. A provider which updates lanes one by
+/// one may be sampled in between though, so with `with_skew_window` lanes may change on
+/// different samples, and their signals are buffered until every lane has advanced.
 pub struct RtsmMultiRx<W: Rx, T, F> {
     rx: W,
     factory: F,
     rtsm: Vec<RtsmRxCore<T>>,
+    skew: Option<Skew>,
+}
+
+/// Buffered signals of a tolerant `RtsmMultiRx`.
+struct Skew {
+    window: usize,
+    /// Signals of every lane which are not yielded yet.
+    queues: Vec<VecDeque<Signal>>,
+    /// Samples since some lanes have advanced, but others have not.
+    waiting: usize,
+}
+
+/// Lanes of `RtsmMultiRx` which have not advanced within the skew window, while the others
+/// have. Signals of the others are discarded, so that the lanes are aligned again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StalledLanesError {
+    pub lanes: Vec<usize>,
+}
+
+/// Lanes of `RtsmMultiRx` whose values in a row could not be decoded. Signals of the other
+/// lanes in the row are discarded too, so that the lanes stay aligned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UndecodableLanesError {
+    pub lanes: Vec<usize>,
+}
+
+/// Asynchronous twin of `RtsmTx`.
+#[cfg(feature = "async")]
+pub struct AsyncRtsmTx<X: AsyncTx> {
+    tx: X,
+    rtsm: RtsmEncoder<X::Item>,
+}
+
+/// Asynchronous twin of `RtsmRx`.
+#[cfg(feature = "async")]
+pub struct AsyncRtsmRx<X: AsyncRx> {
+    rx: X,
+    rtsm: RtsmRxCore<X::Item>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -77,6 +233,24 @@ pub trait RtsmTxExt: Tx {
     {
         RtsmTx::new(ranges, self)
     }
+
+    fn rtsm_symbols(self, levels: RtsmLevels<Self::Item>) -> RtsmSymbolTx<Self>
+    where
+        Self: Sized,
+        Self::Item: SignalValue,
+    {
+        RtsmSymbolTx::new(levels, self)
+    }
+
+    fn rtsm_multi<T, F>(self, ranges_factory: F) -> RtsmMultiTx<Self, T, F>
+    where
+        Self: Sized,
+        Self: Tx<Item = Vec<T>>,
+        T: SignalValue,
+        F: FnMut(usize) -> RtsmRanges<T>,
+    {
+        RtsmMultiTx::new(ranges_factory, self)
+    }
 }
 
 impl<X> RtsmTxExt for X where X: Tx {}
@@ -90,12 +264,20 @@ pub trait RtsmRxExt: Rx {
         RtsmRx::new(ranges, self)
     }
 
+    fn rtsm_symbols(self, levels: RtsmLevels<Self::Item>) -> RtsmSymbolRx<Self>
+    where
+        Self: Sized,
+        Self::Item: SignalValue,
+    {
+        RtsmSymbolRx::new(levels, self)
+    }
+
     fn rtsm_multi<T, F>(self, ranges_factory: F) -> RtsmMultiRx<Self, T, F>
     where
         Self: Sized,
         Self: Rx,
         Self::Item: IntoIterator<Item = T>,
-        T: SignalValue + 'static,
+        T: SignalValue + Send + Sync + 'static,
         F: FnMut(usize) -> RtsmRanges<T>,
     {
         RtsmMultiRx::new(ranges_factory, self)
@@ -104,10 +286,39 @@ pub trait RtsmRxExt: Rx {
 
 impl<X> RtsmRxExt for X where X: Rx {}
 
+#[cfg(feature = "async")]
+pub trait AsyncRtsmTxExt: AsyncTx {
+    fn rtsm(self, ranges: RtsmRanges<Self::Item>) -> AsyncRtsmTx<Self>
+    where
+        Self: Sized,
+        Self::Item: SignalValue,
+    {
+        AsyncRtsmTx::new(ranges, self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<X> AsyncRtsmTxExt for X where X: AsyncTx {}
+
+#[cfg(feature = "async")]
+pub trait AsyncRtsmRxExt: AsyncRx {
+    fn rtsm(self, ranges: RtsmRanges<Self::Item>) -> AsyncRtsmRx<Self>
+    where
+        Self: Sized,
+        Self::Item: SignalValue,
+    {
+        AsyncRtsmRx::new(ranges, self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<X> AsyncRtsmRxExt for X where X: AsyncRx {}
+
 mod imp {
     use super::*;
-    use std::error::Error;
-    use std::fmt;
+    use core::error::Error;
+    use core::fmt;
+    use core::str::FromStr;
 
     fn ranges_are_valid<T: SignalValue>(r1: &Range<T>, r2: &Range<T>) -> bool {
         // |...r1...|
@@ -147,6 +358,172 @@ mod imp {
         }
     }
 
+    impl<T: SignalValue> RtsmLevels<T> {
+        /// Levels in the order of their symbols. Needs at least 2 ranges, every pair of which
+        /// would make valid `RtsmRanges`.
+        pub fn new(ranges: Vec<Range<T>>) -> Result<Self, InvalidRangesError> {
+            let valid = ranges.len() >= 2
+                && ranges
+                    .iter()
+                    .enumerate()
+                    .all(|(i, r1)| ranges[i + 1..].iter().all(|r2| ranges_are_valid(r1, r2)));
+            if valid {
+                Ok(RtsmLevels { ranges })
+            } else {
+                Err(InvalidRangesError)
+            }
+        }
+
+        /// Size of the alphabet.
+        pub fn levels(&self) -> usize {
+            self.ranges.len()
+        }
+
+        /// Whole bits carried by every symbol, i.e. `floor(log2(levels))`.
+        pub fn bits_per_symbol(&self) -> u32 {
+            self.levels().ilog2()
+        }
+
+        pub fn ranges(&self) -> &[Range<T>] {
+            &self.ranges
+        }
+    }
+
+    /// Number of values in a valid range.
+    fn range_len<T: SignalValue>(range: &Range<T>) -> usize {
+        let last = range.end.clone() - T::one();
+        range.start.wrapping_distance(&last, range) + 1
+    }
+
+    impl<T: SignalValue> RangePlan<T> {
+        /// Plan of `lanes` lanes with `levels` levels each. Every range of it must hold at least 2
+        /// values.
+        pub fn new(
+            space: Range<T>,
+            lanes: usize,
+            levels: usize,
+        ) -> Result<Self, InvalidRangesError> {
+            let valid = space.start < space.end
+                && lanes > 0
+                && levels >= 2
+                && range_len(&space) / lanes / levels >= 2;
+            if valid {
+                Ok(RangePlan {
+                    space,
+                    lanes,
+                    levels,
+                })
+            } else {
+                Err(InvalidRangesError)
+            }
+        }
+
+        /// Plan of binary `RtsmRanges`.
+        pub fn binary(space: Range<T>, lanes: usize) -> Result<Self, InvalidRangesError> {
+            RangePlan::new(space, lanes, 2)
+        }
+
+        pub fn lanes(&self) -> usize {
+            self.lanes
+        }
+
+        pub fn levels(&self) -> usize {
+            self.levels
+        }
+
+        /// Number of values in each range.
+        pub fn range_len(&self) -> usize {
+            range_len(&self.space) / self.lanes / self.levels
+        }
+    }
+
+    // ranges are measured out by adding up their length, so `T` must support that
+    impl<T> RangePlan<T>
+    where
+        T: SignalValue + Add<Output = T> + TryFrom<usize>,
+    {
+        /// Levels of the lane, reusing blocks for lanes beyond `lanes()`.
+        pub fn lane_levels(&self, lane: usize) -> RtsmLevels<T> {
+            let Ok(len) = T::try_from(self.range_len()) else {
+                unreachable!("there are at least 2 ranges, so each is at most half of `T`")
+            };
+            let mut start = self.space.start.clone();
+            for _ in 0..(lane % self.lanes) * self.levels {
+                start = start + len.clone();
+            }
+            let ranges = (0..self.levels)
+                .map(|_| {
+                    let end = start.clone() + len.clone();
+                    let range = start.clone()..end.clone();
+                    start = end;
+                    range
+                })
+                .collect();
+            RtsmLevels { ranges }
+        }
+
+        /// Binary ranges of the lane.
+        ///
+        /// # Panics
+        ///
+        /// Panics unless the plan has 2 levels.
+        pub fn lane_ranges(&self, lane: usize) -> RtsmRanges<T> {
+            assert_eq!(self.levels, 2, "RtsmRanges need a plan of 2 levels");
+            let mut ranges = self.lane_levels(lane).ranges.into_iter();
+            let off = ranges.next().unwrap();
+            let on = ranges.next().unwrap();
+            RtsmRanges { off, on }
+        }
+    }
+
+    impl<T: fmt::Display> fmt::Display for RangePlan<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{}..{}/{}x{}",
+                self.space.start, self.space.end, self.lanes, self.levels
+            )
+        }
+    }
+
+    impl<T: SignalValue + FromStr> FromStr for RangePlan<T> {
+        type Err = ParseRangePlanError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let (space, shape) = s.trim().split_once('/').ok_or(ParseRangePlanError)?;
+            let (start, end) = space.split_once("..").ok_or(ParseRangePlanError)?;
+            let (lanes, levels) = shape.split_once('x').ok_or(ParseRangePlanError)?;
+            let start = start.parse().map_err(|_| ParseRangePlanError)?;
+            let end = end.parse().map_err(|_| ParseRangePlanError)?;
+            let lanes = lanes.parse().map_err(|_| ParseRangePlanError)?;
+            let levels = levels.parse().map_err(|_| ParseRangePlanError)?;
+            RangePlan::new(start..end, lanes, levels).map_err(|_| ParseRangePlanError)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    impl<T: fmt::Display> serde::Serialize for RangePlan<T> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(self)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    impl<'de, T: SignalValue + FromStr> serde::Deserialize<'de> for RangePlan<T> {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let s = String::deserialize(deserializer)?;
+            s.parse().map_err(serde::de::Error::custom)
+        }
+    }
+
+    impl<T> From<RtsmRanges<T>> for RtsmLevels<T> {
+        fn from(ranges: RtsmRanges<T>) -> Self {
+            RtsmLevels {
+                ranges: vec![ranges.off, ranges.on],
+            }
+        }
+    }
+
     impl<T> From<Range<T>> for RangeValue<T>
     where
         T: Clone,
@@ -173,51 +550,63 @@ mod imp {
                     }
                     next
                 }
+
+                fn wrapping_distance(&self, to: &Self, range: &Range<Self>) -> usize {
+                    // wide enough for the span of any range of any of these types
+                    let span = range.end as i128 - range.start as i128;
+                    (*to as i128 - *self as i128).rem_euclid(span) as usize
+                }
             }
         )+};
     }
 
     imp_signal_value!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
 
-    impl<X: Tx> RtsmTx<X>
+    impl<T> RtsmEncoder<T>
     where
-        X::Item: SignalValue,
+        T: SignalValue,
     {
-        pub fn new(ranges: RtsmRanges<X::Item>, tx: X) -> Self {
-            RtsmTx {
-                tx,
-                off: RangeValue::from(ranges.off),
-                on: RangeValue::from(ranges.on),
+        /// Encoder of either `RtsmRanges` or `RtsmLevels`.
+        pub fn new(levels: impl Into<RtsmLevels<T>>) -> Self {
+            RtsmEncoder {
+                levels: levels
+                    .into()
+                    .ranges
+                    .into_iter()
+                    .map(RangeValue::from)
+                    .collect(),
                 current: None,
             }
         }
 
-        fn ranges_for_signal(&mut self, signal: Signal) -> &mut RangeValue<X::Item> {
-            match signal {
-                OFF => &mut self.off,
-                ON => &mut self.on,
+        /// Value for the symbol, or `None` if it is not in the alphabet. Value of the symbol's
+        /// range is incremented only if the symbol repeats, so that receiver notices.
+        pub fn encode_symbol(&mut self, symbol: Symbol) -> Option<T> {
+            let repeats = self.current == Some(symbol);
+            let RangeValue { range, value } = self.levels.get_mut(symbol)?;
+            if repeats {
+                *value = value.wrapping_next(range);
             }
+            self.current = Some(symbol);
+            Some(value.clone())
         }
 
-        // increment current value for range to which the signal belongs to.
-        // it does not write the overall current value of self.
-        fn increment_range_if_needed(&mut self, signal: Signal) -> X::Item {
-            // cache current_signal to satisfy borrow checker.
-            let current = self.current;
-            let RangeValue { range, value } = self.ranges_for_signal(signal);
-            // increment only if signal stays at the same value
-            if let Some(current_signal) = current {
-                if current_signal == signal {
-                    *value = value.wrapping_next(range);
-                }
-            }
-            value.clone()
+        /// Value for the signal, i.e. symbol 0 for `OFF` and symbol 1 for `ON`.
+        pub fn encode(&mut self, signal: Signal) -> T {
+            self.encode_symbol(signal as Symbol)
+                .expect("RTSM levels have at least two symbols")
         }
+    }
 
-        fn encode(&mut self, signal: Signal) -> X::Item {
-            let value = self.increment_range_if_needed(signal);
-            self.current = Some(signal);
-            value
+    impl<X: Tx> RtsmTx<X>
+    where
+        X::Item: SignalValue,
+    {
+        pub fn new(ranges: RtsmRanges<X::Item>, tx: X) -> Self {
+            RtsmTx {
+                tx,
+                rtsm: RtsmEncoder::new(ranges),
+            }
         }
     }
 
@@ -227,51 +616,81 @@ mod imp {
     {
         type Item = Signal;
 
-        fn send(&mut self, signal: Signal) -> Result<(), Box<dyn Error>> {
-            let value = self.encode(signal);
+        fn send(&mut self, signal: Signal) -> Result<(), FlowError> {
+            let value = self.rtsm.encode(signal);
             self.tx.send(value)
         }
+
+        fn flush(&mut self) -> Result<(), FlowError> {
+            self.tx.flush()
+        }
+
+        fn close(&mut self) -> Result<(), FlowError> {
+            self.tx.close()
+        }
     }
 
     impl<T> RtsmRxCore<T>
     where
         T: SignalValue,
     {
-        pub fn new(ranges: RtsmRanges<T>) -> Self {
+        pub fn new(levels: RtsmLevels<T>) -> Self {
             RtsmRxCore {
-                off: ranges.off,
-                on: ranges.on,
+                values: vec![None; levels.ranges.len()],
+                levels: levels.ranges,
                 last: None,
             }
         }
 
-        fn signal_for_value(&self, value: T) -> Result<Signal, ()> {
-            if self.off.contains(&value) {
-                Ok(OFF)
-            } else if self.on.contains(&value) {
-                Ok(ON)
-            } else {
-                Err(())
-            }
-        }
-
-        pub fn decode(&mut self, value: T) -> Result<Option<Signal>, DecodeError<T>> {
-            match &self.last {
+        /// Symbol of a new value, and how many ticks it has been since the last value.
+        ///
+        /// Gap is measured from the last value of the same symbol: a repeated symbol is one tick
+        /// past it, and a symbol which comes back carries on from it. Symbol which was never seen,
+        /// or comes right after an undecodable value, counts as one tick.
+        pub fn decode_ticks(
+            &mut self,
+            value: T,
+        ) -> Result<Option<(Symbol, usize)>, DecodeError<T>> {
+            let Some(symbol) = self.levels.iter().position(|range| range.contains(&value)) else {
+                self.last = None;
+                return Err(DecodeError(value));
+            };
+            let ticks = match (&self.values[symbol], self.last) {
                 // signal stays still
-                Some(last_value) if &value == last_value => Ok(None),
-                _ => {
-                    // different value appeared
-                    match self.signal_for_value(value.clone()) {
-                        Ok(signal) => {
-                            self.last = Some(value);
-                            Ok(Some(signal))
-                        }
-                        Err(_) => {
-                            self.last = None;
-                            Err(DecodeError(value))
-                        }
+                (Some(previous), Some(last)) if last == symbol && previous == &value => {
+                    return Ok(None);
+                }
+                (Some(previous), Some(last)) => {
+                    let distance = previous.wrapping_distance(&value, &self.levels[symbol]);
+                    if last == symbol {
+                        distance
+                    } else {
+                        distance + 1
                     }
                 }
+                _ => 1,
+            };
+            self.values[symbol] = Some(value);
+            self.last = Some(symbol);
+            Ok(Some((symbol, ticks)))
+        }
+
+        pub fn decode_symbol(&mut self, value: T) -> Result<Option<Symbol>, DecodeError<T>> {
+            Ok(self.decode_ticks(value)?.map(|(symbol, _)| symbol))
+        }
+
+        pub fn decode(&mut self, value: T) -> Result<Option<Signal>, DecodeError<T>> {
+            let symbol = self.decode_symbol(value)?;
+            Ok(symbol.map(|symbol| symbol == ON as Symbol))
+        }
+
+        /// Fails if `ticks` within the range of the symbol could have wrapped around it.
+        pub fn check_gap(&self, symbol: Symbol, ticks: usize) -> Result<(), AmbiguousGapError> {
+            let range_len = range_len(&self.levels[symbol]);
+            if ticks > range_len / 2 {
+                Err(AmbiguousGapError { ticks, range_len })
+            } else {
+                Ok(())
             }
         }
     }
@@ -283,20 +702,184 @@ mod imp {
         pub fn new(ranges: RtsmRanges<X::Item>, rx: X) -> Self {
             RtsmRx {
                 rx,
-                rtsm: RtsmRxCore::new(ranges),
+                rtsm: RtsmRxCore::new(ranges.into()),
+                policy: LostSamplePolicy::Ignore,
+                lost: 0,
+                repeat: None,
             }
         }
+
+        /// Set what to do with lost samples. Default is `LostSamplePolicy::Ignore`.
+        pub fn with_lost_sample_policy(mut self, policy: LostSamplePolicy) -> Self {
+            self.policy = policy;
+            self
+        }
+
+        /// How many samples have been lost so far, as told by the gaps between values.
+        pub fn lost_samples(&self) -> u64 {
+            self.lost
+        }
     }
 
     impl<X: Rx> Rx for RtsmRx<X>
     where
-        X::Item: SignalValue + 'static,
+        X::Item: SignalValue + Send + Sync + 'static,
+    {
+        type Item = Signal;
+
+        imp_recv_modes!(self, mode => {
+            if let Some((signal, times)) = self.repeat.take() {
+                if times > 1 {
+                    self.repeat = Some((signal, times - 1));
+                }
+                return Ok(Some(signal));
+            }
+            loop {
+                match mode.recv(&mut self.rx)? {
+                    None => return Ok(None),
+                    Some(value) => match self.rtsm.decode_ticks(value)? {
+                        None => { /* repeat with next inner value */ }
+                        Some((symbol, ticks)) => {
+                            let signal = symbol == ON as Symbol;
+                            self.lost += ticks as u64 - 1;
+                            if self.policy == LostSamplePolicy::Reconstruct && ticks > 1 {
+                                if let Err(e) = self.rtsm.check_gap(symbol, ticks) {
+                                    // only the gap is unknown, the value itself comes next
+                                    self.repeat = Some((signal, 1));
+                                    return Err(FlowError::protocol(e));
+                                }
+                                self.repeat = Some((signal, ticks - 1));
+                            }
+                            return Ok(Some(signal));
+                        }
+                    },
+                }
+            }
+        });
+    }
+
+    impl<X: Tx> RtsmSymbolTx<X>
+    where
+        X::Item: SignalValue,
+    {
+        pub fn new(levels: RtsmLevels<X::Item>, tx: X) -> Self {
+            RtsmSymbolTx {
+                tx,
+                rtsm: RtsmEncoder::new(levels),
+            }
+        }
+    }
+
+    impl<X: Tx> Tx for RtsmSymbolTx<X>
+    where
+        X::Item: SignalValue,
+    {
+        type Item = Symbol;
+
+        fn send(&mut self, symbol: Symbol) -> Result<(), FlowError> {
+            match self.rtsm.encode_symbol(symbol) {
+                Some(value) => self.tx.send(value),
+                None => Err(FlowError::protocol(InvalidSymbolError(symbol))),
+            }
+        }
+
+        fn flush(&mut self) -> Result<(), FlowError> {
+            self.tx.flush()
+        }
+
+        fn close(&mut self) -> Result<(), FlowError> {
+            self.tx.close()
+        }
+    }
+
+    impl<X: Rx> RtsmSymbolRx<X>
+    where
+        X::Item: SignalValue,
+    {
+        pub fn new(levels: RtsmLevels<X::Item>, rx: X) -> Self {
+            RtsmSymbolRx {
+                rx,
+                rtsm: RtsmRxCore::new(levels),
+            }
+        }
+    }
+
+    impl<X: Rx> Rx for RtsmSymbolRx<X>
+    where
+        X::Item: SignalValue + Send + Sync + 'static,
+    {
+        type Item = Symbol;
+
+        imp_recv_modes!(self, mode => {
+            loop {
+                match mode.recv(&mut self.rx)? {
+                    None => return Ok(None),
+                    Some(value) => match self.rtsm.decode_symbol(value)? {
+                        None => { /* repeat with next inner value */ }
+                        Some(symbol) => return Ok(Some(symbol)),
+                    },
+                }
+            }
+        });
+    }
+
+    #[cfg(feature = "async")]
+    impl<X: AsyncTx> AsyncRtsmTx<X>
+    where
+        X::Item: SignalValue,
+    {
+        pub fn new(ranges: RtsmRanges<X::Item>, tx: X) -> Self {
+            AsyncRtsmTx {
+                tx,
+                rtsm: RtsmEncoder::new(ranges),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl<X: AsyncTx> AsyncTx for AsyncRtsmTx<X>
+    where
+        X::Item: SignalValue,
+    {
+        type Item = Signal;
+
+        async fn send(&mut self, signal: Signal) -> Result<(), FlowError> {
+            let value = self.rtsm.encode(signal);
+            self.tx.send(value).await
+        }
+
+        async fn flush(&mut self) -> Result<(), FlowError> {
+            self.tx.flush().await
+        }
+
+        async fn close(&mut self) -> Result<(), FlowError> {
+            self.tx.close().await
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl<X: AsyncRx> AsyncRtsmRx<X>
+    where
+        X::Item: SignalValue,
+    {
+        pub fn new(ranges: RtsmRanges<X::Item>, rx: X) -> Self {
+            AsyncRtsmRx {
+                rx,
+                rtsm: RtsmRxCore::new(ranges.into()),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl<X: AsyncRx> AsyncRx for AsyncRtsmRx<X>
+    where
+        X::Item: SignalValue + Send + Sync + 'static,
     {
         type Item = Signal;
 
-        fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+        async fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
             loop {
-                match self.rx.recv()? {
+                match self.rx.recv().await? {
                     None => return Ok(None),
                     Some(value) => match self.rtsm.decode(value)? {
                         None => { /* repeat with next inner value */ }
@@ -307,11 +890,55 @@ mod imp {
         }
     }
 
+    impl<X, T, F> RtsmMultiTx<X, T, F>
+    where
+        X: Tx<Item = Vec<T>>,
+        T: SignalValue,
+        F: FnMut(usize) -> RtsmRanges<T>,
+    {
+        pub fn new(factory: F, tx: X) -> Self {
+            RtsmMultiTx {
+                tx,
+                factory,
+                rtsm: vec![],
+            }
+        }
+    }
+
+    impl<X, T, F> Tx for RtsmMultiTx<X, T, F>
+    where
+        X: Tx<Item = Vec<T>>,
+        T: SignalValue,
+        F: FnMut(usize) -> RtsmRanges<T>,
+    {
+        type Item = Vec<Signal>;
+
+        fn send(&mut self, signals: Vec<Signal>) -> Result<(), FlowError> {
+            for i in self.rtsm.len()..signals.len() {
+                self.rtsm.push(RtsmEncoder::new((self.factory)(i)));
+            }
+            let values = signals
+                .into_iter()
+                .zip(self.rtsm.iter_mut())
+                .map(|(signal, rtsm)| rtsm.encode(signal))
+                .collect();
+            self.tx.send(values)
+        }
+
+        fn flush(&mut self) -> Result<(), FlowError> {
+            self.tx.flush()
+        }
+
+        fn close(&mut self) -> Result<(), FlowError> {
+            self.tx.close()
+        }
+    }
+
     impl<W, T, F> RtsmMultiRx<W, T, F>
     where
         W: Rx,
         W::Item: IntoIterator<Item = T>,
-        T: SignalValue + 'static,
+        T: SignalValue + Send + Sync + 'static,
         F: FnMut(usize) -> RtsmRanges<T>,
     {
         pub fn new(factory: F, rx: W) -> Self {
@@ -319,14 +946,27 @@ mod imp {
                 rx,
                 factory,
                 rtsm: vec![],
+                skew: None,
             }
         }
 
+        /// Let lanes change up to `window` samples later than others. Lanes which take longer
+        /// are reported with `StalledLanesError`, and lanes which received garbage with
+        /// `UndecodableLanesError`.
+        pub fn with_skew_window(mut self, window: usize) -> Self {
+            self.skew = Some(Skew {
+                window,
+                queues: vec![],
+                waiting: 0,
+            });
+            self
+        }
+
         /// Ensure `rtsm[index]` exists by creating it on-demand using the ranges factory.
         fn get_rtsm(&mut self, index: usize) -> &mut RtsmRxCore<T> {
             for i in self.rtsm.len()..=index {
                 let ranges = (self.factory)(i);
-                let core = RtsmRxCore::new(ranges);
+                let core = RtsmRxCore::new(ranges.into());
                 self.rtsm.push(core);
             }
             &mut self.rtsm[index]
@@ -337,14 +977,17 @@ mod imp {
     where
         W: Rx,
         W::Item: IntoIterator<Item = T>,
-        T: SignalValue + 'static,
+        T: SignalValue + Send + Sync + 'static,
         F: FnMut(usize) -> RtsmRanges<<W::Item as IntoIterator>::Item>,
     {
         type Item = Vec<Signal>;
 
-        fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+        imp_recv_modes!(self, mode => {
+            if self.skew.is_some() {
+                return self.recv_skewed(mode);
+            }
             loop {
-                match self.rx.recv()? {
+                match mode.recv(&mut self.rx)? {
                     None => return Ok(None),
                     Some(vec) => {
                         let mut opt = vec![];
@@ -361,10 +1004,87 @@ mod imp {
                     }
                 }
             }
+        });
+    }
+
+    impl<W, T, F> RtsmMultiRx<W, T, F>
+    where
+        W: Rx,
+        W::Item: IntoIterator<Item = T>,
+        T: SignalValue + Send + Sync + 'static,
+        F: FnMut(usize) -> RtsmRanges<T>,
+    {
+        fn recv_skewed(&mut self, mode: RecvMode) -> Result<Option<Vec<Signal>>, FlowError> {
+            loop {
+                let skew = self.skew.as_mut().unwrap();
+                if let Some(row) = skew.pop_row() {
+                    return Ok(Some(row));
+                }
+                let partial = skew.queues.iter().any(|queue| !queue.is_empty());
+                if partial && skew.waiting >= skew.window {
+                    return Err(FlowError::protocol(skew.drop_partial_row()));
+                }
+                // counted only once a row arrives, so that polling does not count as waiting
+                let Some(vec) = mode.recv(&mut self.rx)? else {
+                    return Ok(None);
+                };
+                if partial {
+                    self.skew.as_mut().unwrap().waiting += 1;
+                }
+                // every lane must consume its value, and the row is queued only as a whole,
+                // so that the queues are not misaligned
+                let mut row = vec![];
+                let mut undecodable = vec![];
+                for (i, item) in vec.into_iter().enumerate() {
+                    match self.get_rtsm(i).decode(item) {
+                        Ok(signal) => row.push(signal),
+                        Err(_) => undecodable.push(i),
+                    }
+                }
+                if !undecodable.is_empty() {
+                    return Err(FlowError::protocol(UndecodableLanesError {
+                        lanes: undecodable,
+                    }));
+                }
+                let queues = &mut self.skew.as_mut().unwrap().queues;
+                if queues.len() < row.len() {
+                    queues.resize_with(row.len(), VecDeque::new);
+                }
+                for (queue, signal) in queues.iter_mut().zip(row) {
+                    queue.extend(signal);
+                }
+            }
         }
     }
 
-    fn transform_opt_vec<T>(v: Vec<Option<T>>) -> Result<Option<Vec<T>>, Box<dyn Error>> {
+    impl Skew {
+        /// Signals of every lane, if they have all advanced.
+        fn pop_row(&mut self) -> Option<Vec<Signal>> {
+            if self.queues.is_empty() || self.queues.iter().any(VecDeque::is_empty) {
+                return None;
+            }
+            self.waiting = 0;
+            Some(
+                self.queues
+                    .iter_mut()
+                    .map(|queue| queue.pop_front().unwrap())
+                    .collect(),
+            )
+        }
+
+        fn drop_partial_row(&mut self) -> StalledLanesError {
+            self.waiting = 0;
+            let mut lanes = vec![];
+            for (i, queue) in self.queues.iter_mut().enumerate() {
+                if queue.pop_front().is_none() {
+                    lanes.push(i);
+                }
+            }
+            StalledLanesError { lanes }
+        }
+    }
+
+    fn transform_opt_vec<T>(v: Vec<Option<T>>) -> Result<Option<Vec<T>>, FlowError> {
         // "all" predicates start with `true` until proved otherwise.
         let mut all_some = true;
         let mut all_none = true;
@@ -379,7 +1099,9 @@ mod imp {
             }
         }
         match (all_some, all_none) {
-            (false, false) => Err("Some Rtsm returned value, while others not".into()),
+            (false, false) => Err(FlowError::protocol(
+                "Some Rtsm returned value, while others not",
+            )),
             (true, true) =>
             /* empty vector, nothing to decode */
             {
@@ -407,13 +1129,73 @@ mod imp {
             "failed to decode a signal"
         }
     }
+
+    impl fmt::Display for AmbiguousGapError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "gap of {} ticks is ambiguous in RTSM range of {} values",
+                self.ticks, self.range_len
+            )
+        }
+    }
+
+    impl Error for AmbiguousGapError {}
+
+    impl fmt::Display for StalledLanesError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "RTSM lanes {:?} have stalled", self.lanes)
+        }
+    }
+
+    impl Error for StalledLanesError {}
+
+    impl fmt::Display for UndecodableLanesError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "failed to decode signals of RTSM lanes {:?}", self.lanes)
+        }
+    }
+
+    impl Error for UndecodableLanesError {}
+
+    impl fmt::Display for ParseRangePlanError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            "invalid RTSM range plan, expected `start..end/LANESxLEVELS`".fmt(f)
+        }
+    }
+
+    impl Error for ParseRangePlanError {}
+
+    impl fmt::Display for InvalidRangesError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            "RTSM ranges are empty, too narrow or overlap".fmt(f)
+        }
+    }
+
+    impl Error for InvalidRangesError {}
+
+    impl fmt::Display for InvalidSymbolError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "symbol {} is outside of RTSM alphabet", self.0)
+        }
+    }
+
+    impl Error for InvalidSymbolError {}
+
+    impl<T: Send + Sync + 'static> From<DecodeError<T>> for FlowError {
+        fn from(e: DecodeError<T>) -> Self {
+            FlowError::protocol(e)
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::ToString;
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_rtsm_tx() {
         let (tx, rx) = pair();
         let ranges = RtsmRanges::new(0..10, 20..30).unwrap();
@@ -428,6 +1210,7 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_rtsm_rx_error() {
         let (mut tx, rx) = pair();
         let ranges = RtsmRanges::<u32>::new(0..10, 20..30).unwrap();
@@ -438,7 +1221,8 @@ mod test {
 
         let res = rtsm.recv();
         let err = res.err().unwrap();
-        let dec = err.downcast::<DecodeError<u32>>().unwrap();
+        assert!(err.is_protocol());
+        let dec = err.protocol_error::<DecodeError<u32>>().unwrap();
         assert_eq!(dec.0, 99);
     }
 
@@ -450,6 +1234,7 @@ mod test {
     }
 
     #[test]
+    #[cfg(feature = "std")]
     fn test_encode() {
         // off: 50, 51
         // on: 0, 1, 2
@@ -500,4 +1285,315 @@ mod test {
         let res = rtsm.collect_vec().unwrap();
         assert_eq!(res, signal);
     }
+
+    fn levels() -> RtsmLevels<u32> {
+        RtsmLevels::new(vec![0..10, 10..20, 20..30, 30..40]).unwrap()
+    }
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(levels().levels(), 4);
+        assert_eq!(levels().bits_per_symbol(), 2);
+        let levels16 = RtsmLevels::new((0..16u32).map(|i| i * 4..i * 4 + 4).collect()).unwrap();
+        assert_eq!(levels16.bits_per_symbol(), 4);
+
+        assert!(RtsmLevels::<u32>::new(vec![]).is_err());
+        assert!(RtsmLevels::new(vec![0..10, 10..10]).is_err());
+        assert!(RtsmLevels::new(vec![0..10, 10..20, 15..30]).is_err());
+        assert!(RtsmLevels::new(vec![0..10, 10..11, 20..30]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_symbols() {
+        const SYMBOLS: &[Symbol] = &[3, 0, 0, 2, 1, 1, 1, 3];
+
+        let (tx, rx) = pair();
+        let mut rtsm = tx.rtsm_symbols(levels());
+        rtsm.send_all(SYMBOLS.iter().cloned()).unwrap();
+        drop(rtsm);
+
+        let values = rx.collect_vec().unwrap();
+        assert_eq!(values, &[30, 0, 1, 20, 10, 11, 12, 30]);
+
+        let decoded = IteratorRx::new(values.into_iter())
+            .rtsm_symbols(levels())
+            .collect_vec()
+            .unwrap();
+        assert_eq!(decoded, SYMBOLS);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_invalid_symbol() {
+        let (tx, _rx) = pair::<u32>();
+        let mut rtsm = tx.rtsm_symbols(levels());
+        let err = rtsm.send(4).unwrap_err();
+        assert_eq!(err.protocol_error(), Some(&InvalidSymbolError(4)));
+    }
+
+    #[test]
+    fn test_encoder() {
+        let mut encoder = RtsmEncoder::new(ranges());
+        let values: Vec<_> = SIGNAL
+            .iter()
+            .map(|&signal| encoder.encode(signal))
+            .collect();
+        assert_eq!(values, VALUES);
+
+        let mut encoder = RtsmEncoder::new(levels());
+        assert_eq!(encoder.encode_symbol(3), Some(30));
+        assert_eq!(encoder.encode_symbol(3), Some(31));
+        assert_eq!(encoder.encode_symbol(4), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_multi_tx() {
+        let signals = vec![
+            vec![ON, OFF, ON],
+            vec![OFF, ON, OFF],
+            vec![ON, ON, ON],
+            vec![OFF, ON, ON],
+        ];
+        let ranges = |i: usize| {
+            let i = i as u32;
+            RtsmRanges::new(i * 100..i * 100 + 10, i * 100 + 50..i * 100 + 60).unwrap()
+        };
+
+        let (tx, rx) = pair();
+        let mut rtsm = tx.rtsm_multi(ranges);
+        rtsm.send_all(signals.clone()).unwrap();
+        drop(rtsm);
+
+        let values = rx.collect_vec().unwrap();
+        assert_eq!(values[2], &[50, 151, 250]);
+        assert_eq!(values[3], &[0, 152, 251]);
+
+        let decoded = IteratorRx::new(values.into_iter())
+            .rtsm_multi(ranges)
+            .collect_vec()
+            .unwrap();
+        assert_eq!(decoded, signals);
+    }
+
+    const LOSSY_SIGNALS: &[Signal] = &[OFF, ON, ON, ON, OFF, OFF, ON, ON, ON, ON, ON];
+
+    /// Values sent by `RtsmTx` for `LOSSY_SIGNALS`, except for the `missed` ones.
+    fn lossy_values(ranges: &RtsmRanges<u32>, missed: &[usize]) -> Vec<u32> {
+        let mut values = Vec::new();
+        let mut tx = VecCollectorTx::new(&mut values).rtsm(ranges.clone());
+        tx.send_all(LOSSY_SIGNALS.iter().copied()).unwrap();
+        drop(tx);
+        assert_eq!(values, &[0, 50, 51, 52, 0, 1, 52, 53, 54, 55, 56]);
+        (values.into_iter().enumerate())
+            .filter(|(i, _)| !missed.contains(i))
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    fn assert_lost_samples(missed: &[usize]) {
+        let ranges = RtsmRanges::new(0..10, 50..60).unwrap();
+        let values = lossy_values(&ranges, missed);
+        let received: Vec<_> = (LOSSY_SIGNALS.iter().enumerate())
+            .filter(|(i, _)| !missed.contains(i))
+            .map(|(_, signal)| *signal)
+            .collect();
+
+        let mut rtsm = RtsmRx::new(ranges.clone(), IteratorRx::from(values.clone()));
+        assert_eq!((&mut rtsm).collect_vec().unwrap(), received);
+        assert_eq!(rtsm.lost_samples(), missed.len() as u64);
+
+        let mut rtsm = RtsmRx::new(ranges, IteratorRx::from(values))
+            .with_lost_sample_policy(LostSamplePolicy::Reconstruct);
+        assert_eq!((&mut rtsm).collect_vec().unwrap(), LOSSY_SIGNALS);
+        assert_eq!(rtsm.lost_samples(), missed.len() as u64);
+    }
+
+    #[test]
+    fn test_lost_samples() {
+        assert_lost_samples(&[2, 8, 9]);
+    }
+
+    #[test]
+    fn test_lost_samples_at_symbol_change() {
+        // first OFF after ONs, and first ON after OFFs
+        assert_lost_samples(&[4, 6]);
+    }
+
+    #[test]
+    fn test_lost_samples_wraparound() {
+        let ranges = RtsmRanges::new(0..10, 10..20).unwrap();
+        let rx = IteratorRx::from(vec![18, 11, 15, 12])
+            .rtsm(ranges)
+            .with_lost_sample_policy(LostSamplePolicy::Reconstruct);
+        let mut signals = vec![];
+        let err = rx
+            .map(|signal| signals.push(signal))
+            .collect_vec()
+            .unwrap_err();
+        // 18 -> 11 wraps around; 15 -> 12 is 7 ticks, or the value went back
+        assert_eq!(signals, &[ON, ON, ON, ON, ON, ON, ON, ON]);
+        let gap = err.protocol_error::<AmbiguousGapError>().unwrap();
+        assert_eq!(gap.ticks, 7);
+        assert_eq!(gap.range_len, 10);
+    }
+
+    #[test]
+    fn test_ambiguous_gap_keeps_signal() {
+        let ranges = RtsmRanges::new(0..10, 10..20).unwrap();
+        let mut rx = IteratorRx::from(vec![15, 12, 2])
+            .rtsm(ranges)
+            .with_lost_sample_policy(LostSamplePolicy::Reconstruct);
+        assert_eq!(rx.recv().unwrap(), Some(ON));
+        let err = rx.recv().unwrap_err();
+        assert!(err.protocol_error::<AmbiguousGapError>().is_some());
+        // the value after the gap is not lost
+        assert_eq!(rx.recv().unwrap(), Some(ON));
+        assert_eq!(rx.recv().unwrap(), Some(OFF));
+        assert_eq!(rx.recv().unwrap(), None);
+    }
+
+    /// Signal value which only implements the required methods.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd)]
+    struct Level(u8);
+
+    impl Sub for Level {
+        type Output = Level;
+
+        fn sub(self, other: Level) -> Level {
+            Level(self.0 - other.0)
+        }
+    }
+
+    impl SignalValue for Level {
+        fn one() -> Self {
+            Level(1)
+        }
+
+        fn wrapping_next(&self, range: &Range<Self>) -> Self {
+            let next = Level(self.0 + 1);
+            if range.contains(&next) {
+                next
+            } else {
+                range.start
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_wrapping_distance() {
+        let range = Level(10)..Level(20);
+        assert_eq!(Level(12).wrapping_distance(&Level(15), &range), 3);
+        assert_eq!(Level(18).wrapping_distance(&Level(11), &range), 3);
+        let mut rx = IteratorRx::from(vec![Level(18), Level(11)])
+            .rtsm(RtsmRanges::new(Level(0)..Level(10), range).unwrap());
+        assert_eq!(rx.recv().unwrap(), Some(ON));
+        assert_eq!(rx.recv().unwrap(), Some(ON));
+        assert_eq!(rx.lost_samples(), 2);
+    }
+
+    #[test]
+    fn test_range_plan() {
+        let plan = RangePlan::binary(10..90, 4).unwrap();
+        assert_eq!(plan.range_len(), 10);
+        assert_eq!(plan.lane_levels(0).ranges(), &[10..20, 20..30]);
+        assert_eq!(plan.lane_levels(3).ranges(), &[70..80, 80..90]);
+        // lanes beyond the plan reuse its blocks
+        assert_eq!(plan.lane_levels(5).ranges(), plan.lane_levels(1).ranges());
+        let ranges = plan.lane_ranges(2);
+        assert_eq!((ranges.off, ranges.on), (50..60, 60..70));
+
+        let plan = RangePlan::new(0..u32::MAX, 3, 16).unwrap();
+        let levels = plan.lane_levels(2);
+        assert_eq!(levels.bits_per_symbol(), 4);
+        assert_eq!(levels.ranges()[15].end, 48 * plan.range_len() as u32);
+        // the plan is valid as a whole
+        let all: Vec<_> = (0..3)
+            .flat_map(|lane| plan.lane_levels(lane).ranges)
+            .collect();
+        assert!(RtsmLevels::new(all).is_ok());
+
+        assert!(RangePlan::binary(0..7, 2).is_err());
+        assert!(RangePlan::new(0..100, 0, 2).is_err());
+        assert!(RangePlan::new(0..100, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_range_plan_text() {
+        let plan: RangePlan<u32> = "10..90/4x2".parse().unwrap();
+        assert_eq!(plan, RangePlan::binary(10..90, 4).unwrap());
+        assert_eq!(plan.to_string(), "10..90/4x2");
+
+        assert!("10..90/4".parse::<RangePlan<u32>>().is_err());
+        assert!("10..90/4x1".parse::<RangePlan<u32>>().is_err());
+        assert!("-10..90/4x2".parse::<RangePlan<u32>>().is_err());
+    }
+
+    #[test]
+    fn test_multi_skew() {
+        // provider updates lane 0 before lane 1, and gets sampled in between
+        let values = vec![
+            vec![10, 0],
+            vec![0, 0],
+            vec![0, 10],
+            vec![10, 10],
+            vec![11, 11],
+        ];
+        let ranges = RtsmRanges::new(0..10, 10..20).unwrap();
+
+        let strict = IteratorRx::new(values.clone().into_iter()).rtsm_multi(|_| ranges.clone());
+        assert!(strict.collect_vec().is_err());
+
+        let tolerant = IteratorRx::new(values.into_iter())
+            .rtsm_multi(|_| ranges.clone())
+            .with_skew_window(1);
+        let res = tolerant.collect_vec().unwrap();
+        assert_eq!(res, [[ON, OFF], [OFF, ON], [ON, ON]]);
+    }
+
+    #[test]
+    fn test_multi_stalled() {
+        // lane 1 is stuck, while lane 0 keeps going
+        let values = vec![
+            vec![10, 0],
+            vec![0, 0],
+            vec![10, 0],
+            vec![11, 0],
+            vec![12, 10],
+        ];
+        let ranges = RtsmRanges::new(0..10, 10..20).unwrap();
+        let mut rtsm = IteratorRx::new(values.into_iter())
+            .rtsm_multi(|_| ranges.clone())
+            .with_skew_window(1);
+
+        assert_eq!(rtsm.recv().unwrap(), Some(vec![ON, OFF]));
+        // signals of lane 0 are discarded one by one, while lane 1 lags behind
+        for _ in 0..2 {
+            let err = rtsm.recv().unwrap_err();
+            let stalled = err.protocol_error::<StalledLanesError>().unwrap();
+            assert_eq!(stalled.lanes, &[1]);
+        }
+        assert_eq!(rtsm.recv().unwrap(), Some(vec![ON, ON]));
+        assert_eq!(rtsm.recv().unwrap(), None);
+    }
+
+    #[test]
+    fn test_multi_skew_garbage() {
+        // lane 1 receives garbage in the middle of the second row
+        let values = vec![vec![10, 10], vec![0, 99], vec![1, 1], vec![11, 11]];
+        let ranges = RtsmRanges::new(0..10, 10..20).unwrap();
+        let mut rtsm = IteratorRx::new(values.into_iter())
+            .rtsm_multi(|_| ranges.clone())
+            .with_skew_window(1);
+
+        assert_eq!(rtsm.recv().unwrap(), Some(vec![ON, ON]));
+        let err = rtsm.recv().unwrap_err();
+        let undecodable = err.protocol_error::<UndecodableLanesError>().unwrap();
+        assert_eq!(undecodable.lanes, &[1]);
+        // the whole row is discarded, so the lanes stay aligned
+        assert_eq!(rtsm.recv().unwrap(), Some(vec![OFF, OFF]));
+        assert_eq!(rtsm.recv().unwrap(), Some(vec![ON, ON]));
+        assert_eq!(rtsm.recv().unwrap(), None);
+    }
 }
@@ -1,14 +1,119 @@
-use std::error::Error;
-use std::iter::FromIterator;
-use std::time::Duration;
+use alloc::collections::VecDeque;
+use core::iter::FromIterator;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
 use super::*;
 
+/// How an adapter polls its inner receivers: the same way it is polled itself, so that
+/// `try_recv()` or `recv_timeout()` of a chain does not block in any part of it.
+#[derive(Clone, Copy, Debug)]
+pub enum RecvMode {
+    Block,
+    Try,
+    /// Wait until the deadline. Built by `RecvMode::timeout()`.
+    Timeout(RecvDeadline),
+}
+
+/// Deadline of `RecvMode::Timeout`, shared by all inner receivers. Without a clock to measure
+/// it (i.e. without `std`), every inner receiver may wait up to the whole timeout.
+#[derive(Clone, Copy, Debug)]
+pub struct RecvDeadline {
+    timeout: Duration,
+    #[cfg(feature = "std")]
+    at: Instant,
+}
+
+impl RecvDeadline {
+    /// Timeout the deadline was set with.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// How long until the deadline.
+    pub fn remaining(&self) -> Duration {
+        #[cfg(feature = "std")]
+        return self.at.saturating_duration_since(Instant::now());
+        #[cfg(not(feature = "std"))]
+        return self.timeout;
+    }
+}
+
+impl RecvMode {
+    /// Mode of `recv_timeout(timeout)`.
+    pub fn timeout(timeout: Duration) -> Self {
+        #[cfg(feature = "std")]
+        return Instant::now()
+            .checked_add(timeout)
+            .map_or(RecvMode::Block, |at| {
+                RecvMode::Timeout(RecvDeadline { timeout, at })
+            });
+        #[cfg(not(feature = "std"))]
+        return RecvMode::Timeout(RecvDeadline { timeout });
+    }
+
+    pub fn recv<R: Rx + ?Sized>(self, rx: &mut R) -> Result<Option<R::Item>, FlowError> {
+        match self {
+            RecvMode::Block => rx.recv(),
+            RecvMode::Try => rx.try_recv(),
+            RecvMode::Timeout(deadline) => rx.recv_timeout(deadline.remaining()),
+        }
+    }
+}
+
+/// Implement `recv()`, `try_recv()` and `recv_timeout()` of an adapter with the same body,
+/// which polls inner receivers with the given `RecvMode`.
+macro_rules! imp_recv_modes {
+    ($self:ident, $mode:ident => $body:block) => {
+        fn recv(&mut $self) -> Result<Option<Self::Item>, $crate::FlowError> {
+            let $mode = $crate::rx::RecvMode::Block;
+            $body
+        }
+
+        fn try_recv(&mut $self) -> Result<Option<Self::Item>, $crate::FlowError> {
+            let $mode = $crate::rx::RecvMode::Try;
+            $body
+        }
+
+        fn recv_timeout(
+            &mut $self,
+            timeout: core::time::Duration,
+        ) -> Result<Option<Self::Item>, $crate::FlowError> {
+            let $mode = $crate::rx::RecvMode::timeout(timeout);
+            $body
+        }
+    };
+}
+pub(crate) use imp_recv_modes;
+
 pub trait Rx {
     type Item;
 
     /// Blocking receive value.
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>>;
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError>;
+
+    /// Non-blocking receive value. Fails with `FlowError::Empty` if no value is available yet.
+    ///
+    /// Default implementation falls back to blocking `recv`, which is the right thing for
+    /// receivers which never block anyway (e.g. `IteratorRx`). Adapters poll their inner
+    /// receivers with `try_recv` too.
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.recv()
+    }
+
+    /// Receive value, waiting at most `timeout` for it to arrive. Fails with `FlowError::Timeout`
+    /// if nothing arrived in time.
+    ///
+    /// Default implementation falls back to blocking `recv`. Adapters which need several inner
+    /// values wait for all of them until the same deadline.
+    fn recv_timeout(&mut self, _timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        self.recv()
+    }
 
     fn deduplicate(self) -> DeduplicateRx<Self>
     where
@@ -24,6 +129,7 @@ pub trait Rx {
         FuseRx::new(self)
     }
 
+    #[cfg(feature = "std")]
     fn interval(self, rate: Duration) -> Interval<Self, IntervalRoleRx>
     where
         Self: Sized,
@@ -31,6 +137,44 @@ pub trait Rx {
         Interval::new(self, rate)
     }
 
+    /// Fail with `FlowError::Cancelled` once `cancellation_token` is cancelled.
+    ///
+    /// Cancellation is checked before each call, so to interrupt a call blocked inside, also
+    /// give the token to the blocking stage (e.g. `ReceiverRx::with_cancellation`).
+    #[cfg(feature = "std")]
+    fn cancel_on(self, cancellation_token: CancellationToken) -> CancellableRx<Self>
+    where
+        Self: Sized,
+    {
+        CancellableRx::new(cancellation_token, self)
+    }
+
+    /// Drift-free interval with ticks anchored at `start + n * rate`.
+    #[cfg(feature = "std")]
+    fn interval_fixed(
+        self,
+        rate: Duration,
+        policy: MissedTickPolicy,
+    ) -> Interval<Self, IntervalRoleRx>
+    where
+        Self: Sized,
+    {
+        Interval::new(self, rate).with_schedule(Schedule::Fixed(policy))
+    }
+
+    /// Same as `interval`, but measures time with the given `clock`.
+    #[cfg(feature = "std")]
+    fn interval_with_clock<C: Clock>(
+        self,
+        rate: Duration,
+        clock: C,
+    ) -> Interval<Self, IntervalRoleRx, C>
+    where
+        Self: Sized,
+    {
+        Interval::with_clock(self, rate, clock)
+    }
+
     fn map<F>(self, f: F) -> MapRx<Self, F>
     where
         Self: Sized,
@@ -53,14 +197,306 @@ pub trait Rx {
         FlattenRx::new(self)
     }
 
-    fn collect<B: FromIterator<Self::Item>>(self) -> Result<B, Box<dyn Error>>
+    fn filter<P>(self, predicate: P) -> FilterRx<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        FilterRx::new(self, predicate)
+    }
+
+    fn filter_map<F, U>(self, f: F) -> FilterMapRx<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(Self::Item) -> Option<U>,
+    {
+        FilterMapRx::new(self, f)
+    }
+
+    /// Yield at most `n` values. Inner Rx is not polled after that.
+    fn take(self, n: usize) -> TakeRx<Self>
+    where
+        Self: Sized,
+    {
+        TakeRx::new(self, n)
+    }
+
+    /// Discard first `n` values.
+    fn skip(self, n: usize) -> SkipRx<Self>
+    where
+        Self: Sized,
+    {
+        SkipRx::new(self, n)
+    }
+
+    /// Yield values while `predicate` holds. The first value which fails it is discarded, and
+    /// the inner Rx is not polled after that.
+    fn take_while<P>(self, predicate: P) -> TakeWhileRx<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        TakeWhileRx::new(self, predicate)
+    }
+
+    /// Stateful `map`, which ends as soon as `f` returns `None`.
+    fn scan<S, F, U>(self, initial_state: S, f: F) -> ScanRx<Self, S, F>
+    where
+        Self: Sized,
+        F: FnMut(&mut S, Self::Item) -> Option<U>,
+    {
+        ScanRx::new(self, initial_state, f)
+    }
+
+    /// Call `f` with a reference to every value passing through.
+    fn inspect<F>(self, f: F) -> InspectRx<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Item),
+    {
+        InspectRx::new(self, f)
+    }
+
+    /// Yield values of `other` after this one ends.
+    fn chain<R: Rx<Item = Self::Item>>(self, other: R) -> ChainRx<Self, R>
+    where
+        Self: Sized,
+    {
+        ChainRx::new(self, other)
+    }
+
+    /// Pair values up with their index, starting at zero.
+    fn enumerate(self) -> EnumerateRx<Self>
+    where
+        Self: Sized,
+    {
+        EnumerateRx::new(self)
+    }
+
+    /// Sliding windows of the last `size` values, one per every value after the first
+    /// `size - 1` ones.
+    fn window(self, size: usize) -> WindowRx<Self>
+    where
+        Self: Sized,
+        Self::Item: Clone,
+    {
+        WindowRx::new(self, size)
+    }
+
+    /// Group values into vectors of `size` items. This is the Rx mirror of `ChunksTx`, except
+    /// that incomplete trailing batch is yielded as well when the inner Rx ends.
+    fn batch(self, size: usize) -> BatchRx<Self>
+    where
+        Self: Sized,
+    {
+        BatchRx::new(self, size)
+    }
+
+    /// Pair up values of two receivers. Ends as soon as either of them ends.
+    fn zip<R: Rx>(self, other: R) -> ZipRx<Self, R>
+    where
+        Self: Sized,
+    {
+        ZipRx::new(self, other)
+    }
+
+    /// Interleave values of two receivers in round-robin order, until both of them end.
+    fn merge<R: Rx<Item = Self::Item>>(self, other: R) -> MergeRx<Self, R>
+    where
+        Self: Sized,
+    {
+        MergeRx::new(self, other)
+    }
+
+    /// Poll this and `other` receivers on their own threads, and yield whatever value is
+    /// ready first.
+    ///
+    /// The receivers can't see the merger's cancellation token, so a receiver blocked in
+    /// `recv()` (e.g. a pair receiver whose sender is still alive) could not be stopped on drop.
+    /// Hence the threads are detached rather than joined when the merger is dropped, and each
+    /// of them lingers until its receiver returns. Use `ReadyMergeRx::new()` with sources built
+    /// on `cancellation_token()` to have them stopped and joined.
+    #[cfg(feature = "std")]
+    fn merge_ready<R>(self, other: R) -> ReadyMergeRx<Self::Item>
+    where
+        Self: Sized + Send + 'static,
+        Self::Item: Send + 'static,
+        R: Rx<Item = Self::Item> + Send + 'static,
+    {
+        let mut merged = ReadyMergeRx::new().with(self).with(other);
+        merged.threads.detach = true;
+        merged
+    }
+
+    /// Split receiver of pairs into two receivers. Values of one half are buffered until the
+    /// other half is polled, so both halves should be consumed at a similar pace.
+    #[cfg(feature = "std")]
+    fn unzip<A, B>(self) -> (UnzipLeftRx<Self, A, B>, UnzipRightRx<Self, A, B>)
+    where
+        Self: Sized + Rx<Item = (A, B)>,
+    {
+        unzip(self)
+    }
+
+    /// Split receiver of vectors into `lanes` receivers, so that `i`-th lane yields `i`-th
+    /// elements. Buffering caveats of `unzip` apply.
+    #[cfg(feature = "std")]
+    fn split<T>(self, lanes: usize) -> Vec<SplitRx<Self, T>>
+    where
+        Self: Sized + Rx<Item = Vec<T>>,
+    {
+        split(self, lanes)
+    }
+
+    /// Replace errors with values computed by `f`. If `f` returns `None`, the error is
+    /// dropped and the next value is received instead.
+    fn recover<F>(self, f: F) -> RecoverRx<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(FlowError) -> Option<Self::Item>,
+    {
+        RecoverRx::new(self, f)
+    }
+
+    /// Drop errors and keep receiving.
+    ///
+    /// Beware of receivers which keep failing forever, as this adapter would spin on them.
+    fn skip_errors(self) -> SkipErrorsRx<Self>
+    where
+        Self: Sized,
+    {
+        RecoverRx::new(self, |_| None)
+    }
+
+    fn map_err<F>(self, f: F) -> MapErrRx<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(FlowError) -> FlowError,
+    {
+        MapErrRx::new(self, f)
+    }
+
+    /// Report errors to a side channel `tx` and keep receiving. Only errors of `tx` itself
+    /// are returned.
+    fn errors_to<X>(self, tx: X) -> ErrorsToRx<Self, X>
+    where
+        Self: Sized,
+        X: Tx<Item = FlowError>,
+    {
+        ErrorsToRx::new(self, tx)
+    }
+
+    /// Record statistics of this stage into the global `MetricsRegistry` under `name`.
+    #[cfg(feature = "std")]
+    fn instrument(self, name: &str) -> InstrumentedRx<Self>
+    where
+        Self: Sized,
+    {
+        InstrumentedRx::new(self, MetricsRegistry::global().register(name))
+    }
+
+    /// Same as `instrument`, but records into the given `registry`.
+    #[cfg(feature = "std")]
+    fn instrument_in<C: Clock + Clone>(
+        self,
+        name: &str,
+        registry: &MetricsRegistry<C>,
+    ) -> InstrumentedRx<Self, C>
+    where
+        Self: Sized,
+    {
+        InstrumentedRx::new(self, registry.register(name))
+    }
+
+    /// Write every received value with its timing into `writer`. See `RecordingRx`.
+    #[cfg(feature = "record")]
+    fn record<W: std::io::Write>(self, writer: W) -> Result<RecordingRx<Self, W>, FlowError>
+    where
+        Self: Sized,
+    {
+        RecordingRx::new(self, writer)
+    }
+
+    /// Move this chain to its own thread. See `spawn_rx_stage` for restarting on failures.
+    #[cfg(feature = "std")]
+    fn spawn_stage(self) -> SpawnedRx<Self::Item>
+    where
+        Self: Sized + Send + 'static,
+        Self::Item: Send + 'static,
+    {
+        let mut rx = Some(self);
+        spawn_rx_stage(RestartPolicy::Never, move |_| {
+            rx.take().expect("stage is never restarted")
+        })
+    }
+
+    /// Sample values over a simulated lossy channel, treating this receiver as a provider
+    /// which yields a new value every `period`. See `ChannelSimulatorRx`.
+    #[cfg(feature = "std")]
+    fn simulate_channel(
+        self,
+        period: Duration,
+        conditions: ChannelConditions,
+        seed: u64,
+    ) -> ChannelSimulatorRx<Self>
+    where
+        Self: Sized,
+    {
+        ChannelSimulatorRx::new(self, period, conditions, seed)
+    }
+
+    /// Pair every value with the time elapsed since the first one.
+    #[cfg(feature = "std")]
+    fn timestamp(self) -> TimestampRx<Self>
+    where
+        Self: Sized,
+    {
+        TimestampRx::new(self)
+    }
+
+    /// Same as `timestamp`, but measures time with the given `clock`.
+    #[cfg(feature = "std")]
+    fn timestamp_with<C: Clock>(self, clock: C) -> TimestampRx<Self, C>
+    where
+        Self: Sized,
+    {
+        TimestampRx::with_clock(self, clock)
+    }
+
+    /// Convert timestamped values into one value per `period`. See `ResampleMode`.
+    fn resample<T>(self, period: Duration, mode: ResampleMode) -> ResampleRx<Self, T>
+    where
+        Self: Sized + Rx<Item = (Duration, T)>,
+        T: Clone + PartialEq,
+    {
+        ResampleRx::new(self, period, mode)
+    }
+
+    /// Decode bits encoded with `code`, correcting errors where possible. See `FecCode`.
+    fn fec_decode(self, code: FecCode) -> FecDecodeRx<Self>
+    where
+        Self: Sized + Rx<Item = bool>,
+    {
+        FecDecodeRx::new(code, self)
+    }
+
+    /// Hunt for frames in a bit stream, and yield their payload units. See `FrameRx`.
+    fn unframe<U>(self) -> FrameRx<Self, U>
+    where
+        Self: Sized + Rx<Item = bool>,
+        U: FrameUnit,
+    {
+        FrameRx::new(self)
+    }
+
+    fn collect<B: FromIterator<Self::Item>>(self) -> Result<B, FlowError>
     where
         Self: Sized,
     {
         RxIteratorAdapter::new(self).collect()
     }
 
-    fn collect_vec(self) -> Result<Vec<Self::Item>, Box<dyn Error>>
+    fn collect_vec(self) -> Result<Vec<Self::Item>, FlowError>
     where
         Self: Sized,
     {
@@ -68,6 +504,23 @@ pub trait Rx {
     }
 }
 
+/// Lend an Rx to a pipeline without giving up its ownership.
+impl<R: Rx + ?Sized> Rx for &mut R {
+    type Item = R::Item;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        (**self).recv()
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        (**self).try_recv()
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        (**self).recv_timeout(timeout)
+    }
+}
+
 pub struct ConstStringRx {
     string: String,
 }
@@ -81,7 +534,7 @@ impl ConstStringRx {
 impl Rx for ConstStringRx {
     type Item = String;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
         Ok(Some(self.string.clone()))
     }
 }
@@ -99,7 +552,7 @@ impl CounterRx {
 impl Rx for CounterRx {
     type Item = isize;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
         let i = self.i;
         self.i += 1;
         Ok(Some(i))
@@ -124,16 +577,16 @@ where
 {
     type Item = R::Item;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    imp_recv_modes!(self, mode => {
         let new = match self.last.clone() {
             None => {
                 // first time here
-                self.inner.recv()?
+                mode.recv(&mut self.inner)?
             }
             Some(last) => {
                 let mut new = last.clone();
                 while new == last {
-                    new = self.inner.recv()?;
+                    new = mode.recv(&mut self.inner)?;
                 }
                 // at this point new != last
                 new
@@ -142,13 +595,14 @@ where
 
         self.last = Some(new.clone());
         Ok(new)
-    }
+    });
 }
 
-/// Stops polling inner `Rx` after first error, always returning `Ok(None)` afterwards.
+/// Stops polling inner `Rx` after first error, always returning `Ok(None)` afterwards. Running
+/// out of time in `try_recv()` or `recv_timeout()` is not an error.
 pub struct FuseRx<R> {
-    inner: R,
-    error: bool,
+    pub(crate) inner: R,
+    pub(crate) error: bool,
 }
 
 impl<R> FuseRx<R> {
@@ -163,33 +617,81 @@ impl<R> FuseRx<R> {
 impl<R: Rx> Rx for FuseRx<R> {
     type Item = R::Item;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    imp_recv_modes!(self, mode => {
         if self.error {
             Ok(None)
         } else {
-            match self.inner.recv() {
+            match mode.recv(&mut self.inner) {
                 Ok(item) => Ok(item),
+                Err(e) if e.is_not_ready() => Err(e),
                 Err(_) => {
                     self.error = true;
                     Ok(None)
                 }
             }
         }
+    });
+}
+
+#[cfg(feature = "std")]
+impl<R: Rx, C: Clock> Rx for Interval<R, IntervalRoleRx, C> {
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        self.wait_for_tick_with(mode)?;
+        mode.recv(&mut self.inner)
+    });
+}
+
+/// Receives values unless `cancellation_token` is cancelled, in which case it returns
+/// `FlowError::Cancelled` without polling inner `Rx`.
+#[cfg(feature = "std")]
+pub struct CancellableRx<R> {
+    rx: R,
+    cancellation_token: CancellationToken,
+}
+
+#[cfg(feature = "std")]
+impl<R> CancellableRx<R> {
+    pub fn new(cancellation_token: CancellationToken, rx: R) -> Self {
+        CancellableRx {
+            rx,
+            cancellation_token,
+        }
+    }
+
+    fn check(&self) -> Result<(), FlowError> {
+        if self.cancellation_token.is_cancelled() {
+            Err(FlowError::Cancelled)
+        } else {
+            Ok(())
+        }
     }
 }
 
-impl<R: Rx> Rx for Interval<R, IntervalRoleRx> {
+#[cfg(feature = "std")]
+impl<R: Rx> Rx for CancellableRx<R> {
     type Item = R::Item;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
-        self.sleep_and_update_last_call_time();
-        self.inner.recv()
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.check()?;
+        self.rx.recv()
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.check()?;
+        self.rx.try_recv()
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        self.check()?;
+        self.rx.recv_timeout(timeout)
     }
 }
 
 pub struct MapRx<R, F> {
-    inner: R,
-    f: F,
+    pub(crate) inner: R,
+    pub(crate) f: F,
 }
 
 impl<R, F> MapRx<R, F> {
@@ -204,11 +706,21 @@ where
 {
     type Item = U;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
         Ok(self.inner.recv()?.map(&mut self.f))
     }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.inner.try_recv()?.map(&mut self.f))
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.inner.recv_timeout(timeout)?.map(&mut self.f))
+    }
 }
 
+/// Lets `f` receive values of the inner `Rx` however it likes. Since `f` decides how to poll,
+/// `try_recv()` and `recv_timeout()` fall back to `recv()`.
 pub struct AndThenRx<R, F> {
     inner: R,
     f: F,
@@ -222,11 +734,11 @@ impl<R, F> AndThenRx<R, F> {
 
 impl<R: Rx, F, U> Rx for AndThenRx<R, F>
 where
-    F: FnMut(&mut R) -> Result<Option<U>, Box<dyn Error>>,
+    F: FnMut(&mut R) -> Result<Option<U>, FlowError>,
 {
     type Item = U;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
         (self.f)(&mut self.inner)
     }
 }
@@ -251,11 +763,11 @@ where
 {
     type Item = T;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
+    imp_recv_modes!(self, mode => {
         if !self.buffer.is_empty() {
             Ok(Some(self.buffer.remove(0)))
         } else {
-            match self.inner.recv()? {
+            match mode.recv(&mut self.inner)? {
                 None => Ok(None),
                 Some(vec) => {
                     self.buffer = vec;
@@ -267,57 +779,1126 @@ where
                 }
             }
         }
-    }
+    });
 }
 
-pub struct RxIteratorAdapter<R> {
+////////////////////////////////////////////////
+///////////////// Iterator-like ////////////////
+////////////////////////////////////////////////
+
+pub struct FilterRx<R, P> {
     inner: R,
+    predicate: P,
 }
 
-impl<R> RxIteratorAdapter<R> {
-    pub fn new(inner: R) -> Self {
-        RxIteratorAdapter { inner }
+impl<R, P> FilterRx<R, P> {
+    pub fn new(inner: R, predicate: P) -> Self {
+        FilterRx { inner, predicate }
     }
 }
 
-impl<R: Rx> Iterator for RxIteratorAdapter<R> {
-    type Item = Result<R::Item, Box<dyn Error>>;
+impl<R: Rx, P> Rx for FilterRx<R, P>
+where
+    P: FnMut(&R::Item) -> bool,
+{
+    type Item = R::Item;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.inner.recv() {
-            Err(e) => Some(Err(e)),
-            Ok(Some(item)) => Some(Ok(item)),
-            Ok(None) => None,
+    imp_recv_modes!(self, mode => {
+        while let Some(item) = mode.recv(&mut self.inner)? {
+            if (self.predicate)(&item) {
+                return Ok(Some(item));
+            }
         }
-    }
+        Ok(None)
+    });
 }
 
-pub struct IteratorRx<I> {
-    iter: I,
+pub struct FilterMapRx<R, F> {
+    inner: R,
+    f: F,
 }
 
-impl<I> IteratorRx<I> {
-    pub fn new(iter: I) -> Self {
-        IteratorRx { iter }
+impl<R, F> FilterMapRx<R, F> {
+    pub fn new(inner: R, f: F) -> Self {
+        FilterMapRx { inner, f }
     }
 }
 
-impl<I> Rx for IteratorRx<I>
+impl<R: Rx, F, U> Rx for FilterMapRx<R, F>
 where
-    I: Iterator,
+    F: FnMut(R::Item) -> Option<U>,
 {
-    type Item = I::Item;
+    type Item = U;
 
-    fn recv(&mut self) -> Result<Option<Self::Item>, Box<dyn Error>> {
-        Ok(self.iter.next())
-    }
+    imp_recv_modes!(self, mode => {
+        while let Some(item) = mode.recv(&mut self.inner)? {
+            if let Some(mapped) = (self.f)(item) {
+                return Ok(Some(mapped));
+            }
+        }
+        Ok(None)
+    });
 }
 
-impl<I> From<I> for IteratorRx<I::IntoIter>
-where
-    I: IntoIterator,
-{
-    fn from(from: I) -> Self {
+pub struct TakeRx<R> {
+    inner: R,
+    remaining: usize,
+}
+
+impl<R> TakeRx<R> {
+    pub fn new(inner: R, n: usize) -> Self {
+        TakeRx {
+            inner,
+            remaining: n,
+        }
+    }
+}
+
+impl<R: Rx> Rx for TakeRx<R> {
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let item = mode.recv(&mut self.inner)?;
+        if item.is_some() {
+            self.remaining -= 1;
+        }
+        Ok(item)
+    });
+}
+
+pub struct SkipRx<R> {
+    inner: R,
+    remaining: usize,
+}
+
+impl<R> SkipRx<R> {
+    pub fn new(inner: R, n: usize) -> Self {
+        SkipRx {
+            inner,
+            remaining: n,
+        }
+    }
+}
+
+impl<R: Rx> Rx for SkipRx<R> {
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        while self.remaining > 0 {
+            if mode.recv(&mut self.inner)?.is_none() {
+                return Ok(None);
+            }
+            self.remaining -= 1;
+        }
+        mode.recv(&mut self.inner)
+    });
+}
+
+pub struct TakeWhileRx<R, P> {
+    inner: R,
+    predicate: P,
+    done: bool,
+}
+
+impl<R, P> TakeWhileRx<R, P> {
+    pub fn new(inner: R, predicate: P) -> Self {
+        TakeWhileRx {
+            inner,
+            predicate,
+            done: false,
+        }
+    }
+}
+
+impl<R: Rx, P> Rx for TakeWhileRx<R, P>
+where
+    P: FnMut(&R::Item) -> bool,
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        if self.done {
+            return Ok(None);
+        }
+        match mode.recv(&mut self.inner)? {
+            Some(item) if (self.predicate)(&item) => Ok(Some(item)),
+            _ => {
+                self.done = true;
+                Ok(None)
+            }
+        }
+    });
+}
+
+pub struct ScanRx<R, S, F> {
+    inner: R,
+    state: S,
+    f: F,
+    done: bool,
+}
+
+impl<R, S, F> ScanRx<R, S, F> {
+    pub fn new(inner: R, initial_state: S, f: F) -> Self {
+        ScanRx {
+            inner,
+            state: initial_state,
+            f,
+            done: false,
+        }
+    }
+}
+
+impl<R: Rx, S, F, U> Rx for ScanRx<R, S, F>
+where
+    F: FnMut(&mut S, R::Item) -> Option<U>,
+{
+    type Item = U;
+
+    imp_recv_modes!(self, mode => {
+        if self.done {
+            return Ok(None);
+        }
+        let mapped = match mode.recv(&mut self.inner)? {
+            Some(item) => (self.f)(&mut self.state, item),
+            None => None,
+        };
+        self.done = mapped.is_none();
+        Ok(mapped)
+    });
+}
+
+pub struct InspectRx<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F> InspectRx<R, F> {
+    pub fn new(inner: R, f: F) -> Self {
+        InspectRx { inner, f }
+    }
+}
+
+impl<R: Rx, F> Rx for InspectRx<R, F>
+where
+    F: FnMut(&R::Item),
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        let item = mode.recv(&mut self.inner)?;
+        if let Some(ref item) = item {
+            (self.f)(item);
+        }
+        Ok(item)
+    });
+}
+
+pub struct ChainRx<A, B> {
+    first: A,
+    second: B,
+    first_done: bool,
+}
+
+impl<A, B> ChainRx<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        ChainRx {
+            first,
+            second,
+            first_done: false,
+        }
+    }
+}
+
+impl<A, B> Rx for ChainRx<A, B>
+where
+    A: Rx,
+    B: Rx<Item = A::Item>,
+{
+    type Item = A::Item;
+
+    imp_recv_modes!(self, mode => {
+        if !self.first_done {
+            match mode.recv(&mut self.first)? {
+                Some(item) => return Ok(Some(item)),
+                None => self.first_done = true,
+            }
+        }
+        mode.recv(&mut self.second)
+    });
+}
+
+pub struct EnumerateRx<R> {
+    inner: R,
+    count: usize,
+}
+
+impl<R> EnumerateRx<R> {
+    pub fn new(inner: R) -> Self {
+        EnumerateRx { inner, count: 0 }
+    }
+}
+
+impl<R: Rx> Rx for EnumerateRx<R> {
+    type Item = (usize, R::Item);
+
+    imp_recv_modes!(self, mode => {
+        Ok(mode.recv(&mut self.inner)?.map(|item| {
+            let index = self.count;
+            self.count += 1;
+            (index, item)
+        }))
+    });
+}
+
+pub struct WindowRx<R: Rx> {
+    inner: R,
+    size: usize,
+    buffer: VecDeque<R::Item>,
+}
+
+impl<R: Rx> WindowRx<R> {
+    pub fn new(inner: R, size: usize) -> Self {
+        assert_ne!(size, 0, "Window size must not be zero");
+        WindowRx {
+            inner,
+            size,
+            buffer: VecDeque::with_capacity(size),
+        }
+    }
+}
+
+impl<R> Rx for WindowRx<R>
+where
+    R: Rx,
+    R::Item: Clone,
+{
+    type Item = Vec<R::Item>;
+
+    imp_recv_modes!(self, mode => {
+        if self.buffer.len() == self.size {
+            self.buffer.pop_front();
+        }
+        while self.buffer.len() < self.size {
+            match mode.recv(&mut self.inner)? {
+                Some(item) => self.buffer.push_back(item),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(self.buffer.iter().cloned().collect()))
+    });
+}
+
+pub struct BatchRx<R: Rx> {
+    inner: R,
+    size: usize,
+    buffer: Vec<R::Item>,
+}
+
+impl<R: Rx> BatchRx<R> {
+    pub fn new(inner: R, size: usize) -> Self {
+        assert_ne!(size, 0, "Batch size must not be zero");
+        BatchRx {
+            inner,
+            size,
+            buffer: Vec::with_capacity(size),
+        }
+    }
+}
+
+impl<R: Rx> Rx for BatchRx<R> {
+    type Item = Vec<R::Item>;
+
+    imp_recv_modes!(self, mode => {
+        // on error, values received so far stay buffered for the next call
+        while self.buffer.len() < self.size {
+            match mode.recv(&mut self.inner)? {
+                Some(item) => self.buffer.push(item),
+                None => break,
+            }
+        }
+        if self.buffer.is_empty() {
+            Ok(None)
+        } else {
+            let batch = core::mem::replace(&mut self.buffer, Vec::with_capacity(self.size));
+            Ok(Some(batch))
+        }
+    });
+}
+
+////////////////////////////////////////////////
+//////////////// Error Recovery ////////////////
+////////////////////////////////////////////////
+
+pub type SkipErrorsRx<R> = RecoverRx<R, fn(FlowError) -> Option<<R as Rx>::Item>>;
+
+pub struct RecoverRx<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F> RecoverRx<R, F> {
+    pub fn new(inner: R, f: F) -> Self {
+        RecoverRx { inner, f }
+    }
+}
+
+impl<R: Rx, F> Rx for RecoverRx<R, F>
+where
+    F: FnMut(FlowError) -> Option<R::Item>,
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        loop {
+            match mode.recv(&mut self.inner) {
+                Ok(item) => return Ok(item),
+                Err(e) if e.is_not_ready() => return Err(e),
+                Err(e) => match (self.f)(e) {
+                    Some(item) => return Ok(Some(item)),
+                    None => { /* repeat with next inner value */ }
+                },
+            }
+        }
+    });
+}
+
+pub struct MapErrRx<R, F> {
+    inner: R,
+    f: F,
+}
+
+impl<R, F> MapErrRx<R, F> {
+    pub fn new(inner: R, f: F) -> Self {
+        MapErrRx { inner, f }
+    }
+}
+
+impl<R: Rx, F> Rx for MapErrRx<R, F>
+where
+    F: FnMut(FlowError) -> FlowError,
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        match mode.recv(&mut self.inner) {
+            Err(e) if e.is_not_ready() => Err(e),
+            result => result.map_err(&mut self.f),
+        }
+    });
+}
+
+pub struct ErrorsToRx<R, X> {
+    inner: R,
+    tx: X,
+}
+
+impl<R, X> ErrorsToRx<R, X> {
+    pub fn new(inner: R, tx: X) -> Self {
+        ErrorsToRx { inner, tx }
+    }
+}
+
+impl<R: Rx, X> Rx for ErrorsToRx<R, X>
+where
+    X: Tx<Item = FlowError>,
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        loop {
+            match mode.recv(&mut self.inner) {
+                Ok(item) => return Ok(item),
+                Err(e) if e.is_not_ready() => return Err(e),
+                Err(e) => self.tx.send(e)?,
+            }
+        }
+    });
+}
+
+////////////////////////////////////////////////
+//////////////// Fan-in/Fan-out ////////////////
+////////////////////////////////////////////////
+
+pub struct ZipRx<A: Rx, B> {
+    a: A,
+    b: B,
+    /// Value of `a` which is still waiting for its pair, if `b` was not ready in time.
+    pending: Option<A::Item>,
+}
+
+impl<A: Rx, B> ZipRx<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        ZipRx {
+            a,
+            b,
+            pending: None,
+        }
+    }
+}
+
+impl<A: Rx, B: Rx> Rx for ZipRx<A, B> {
+    type Item = (A::Item, B::Item);
+
+    imp_recv_modes!(self, mode => {
+        let a = match self.pending.take() {
+            Some(a) => a,
+            None => match mode.recv(&mut self.a)? {
+                None => return Ok(None),
+                Some(a) => a,
+            },
+        };
+        match mode.recv(&mut self.b) {
+            Err(e) => {
+                self.pending = Some(a);
+                Err(e)
+            }
+            Ok(b) => Ok(b.map(|b| (a, b))),
+        }
+    });
+}
+
+/// Round-robin merge of two receivers. Once one of them ends, the other one is drained.
+pub struct MergeRx<A, B> {
+    a: Option<A>,
+    b: Option<B>,
+    /// Whether `b` should be polled next.
+    b_turn: bool,
+}
+
+impl<A, B> MergeRx<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        MergeRx {
+            a: Some(a),
+            b: Some(b),
+            b_turn: false,
+        }
+    }
+}
+
+impl<A, B> Rx for MergeRx<A, B>
+where
+    A: Rx,
+    B: Rx<Item = A::Item>,
+{
+    type Item = A::Item;
+
+    imp_recv_modes!(self, mode => {
+        // side which was not ready gives its turn to the other one
+        let mut not_ready = None;
+        // at most one turn for each side
+        for _ in 0..2 {
+            let b_turn = self.b_turn;
+            self.b_turn = !b_turn;
+            let item = if b_turn {
+                match self.b {
+                    None => continue,
+                    Some(ref mut b) => mode.recv(b),
+                }
+            } else {
+                match self.a {
+                    None => continue,
+                    Some(ref mut a) => mode.recv(a),
+                }
+            };
+            match item {
+                Ok(Some(item)) => return Ok(Some(item)),
+                Ok(None) if b_turn => self.b = None,
+                Ok(None) => self.a = None,
+                Err(e) if e.is_not_ready() => not_ready = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        match not_ready {
+            Some(e) => Err(e),
+            None => Ok(None),
+        }
+    });
+}
+
+/// First-ready merge of any number of receivers, each polled on its own thread.
+///
+/// Errors of the sources are forwarded as is. Ends when all sources end.
+/// Each thread holds at most one value which was not received yet, and waits for it to be taken
+/// before polling its source again. Dropping it stops the threads and waits for them, so a
+/// source which may block for long should give up once `cancellation_token()` is cancelled.
+#[cfg(feature = "std")]
+pub struct ReadyMergeRx<T> {
+    sender: Option<BoundedSenderTx<Result<T, FlowError>>>,
+    // dropped before the threads are joined, so that none of them is left waiting for room
+    receiver: BoundedReceiverRx<Result<T, FlowError>>,
+    threads: MergeThreads,
+}
+
+#[cfg(feature = "std")]
+struct MergeThreads {
+    cancellation_token: CancellationToken,
+    threads: Vec<thread::JoinHandle<()>>,
+    /// Let the threads finish on their own instead of joining them, for `Rx::merge_ready`.
+    detach: bool,
+}
+
+#[cfg(feature = "std")]
+impl Drop for MergeThreads {
+    fn drop(&mut self) {
+        self.cancellation_token.cancel();
+        if self.detach {
+            return;
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Send + 'static> ReadyMergeRx<T> {
+    pub fn new() -> Self {
+        let (sender, receiver) = bounded(1, OverflowPolicy::Block);
+        ReadyMergeRx {
+            sender: Some(sender),
+            receiver,
+            threads: MergeThreads {
+                cancellation_token: CancellationToken::new(),
+                threads: Vec::new(),
+                detach: false,
+            },
+        }
+    }
+
+    /// Token which is cancelled when this is dropped.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.threads.cancellation_token
+    }
+
+    /// Add another source. Must be called before the first `recv()`.
+    pub fn with<R>(mut self, mut rx: R) -> Self
+    where
+        R: Rx<Item = T> + Send + 'static,
+    {
+        let mut sender = match self.sender {
+            Some(ref sender) => sender.clone(),
+            None => panic!("ReadyMergeRx: sources must be added before receiving"),
+        };
+        let token = self.threads.cancellation_token.clone();
+        self.threads.threads.push(thread::spawn(move || {
+            while !token.is_cancelled() {
+                let Some(item) = rx.recv().transpose() else {
+                    break;
+                };
+                if sender.send(item).is_err() {
+                    break;
+                }
+            }
+        }));
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Send + 'static> Default for ReadyMergeRx<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Rx for ReadyMergeRx<T> {
+    type Item = T;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        // let the channel hang up once all sources end
+        self.sender = None;
+        match self.receiver.recv()? {
+            None => Ok(None),
+            Some(Ok(item)) => Ok(Some(item)),
+            Some(Err(e)) => Err(e),
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.sender = None;
+        match self.receiver.try_recv()? {
+            None => Ok(None),
+            Some(Ok(item)) => Ok(Some(item)),
+            Some(Err(e)) => Err(e),
+        }
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        self.sender = None;
+        match self.receiver.recv_timeout(timeout)? {
+            None => Ok(None),
+            Some(Ok(item)) => Ok(Some(item)),
+            Some(Err(e)) => Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+struct UnzipState<R, A, B> {
+    inner: R,
+    left: VecDeque<A>,
+    right: VecDeque<B>,
+}
+
+#[cfg(feature = "std")]
+impl<R, A, B> UnzipState<R, A, B>
+where
+    R: Rx<Item = (A, B)>,
+{
+    /// Pull one more pair from the inner receiver into the buffers.
+    /// Returns `false` if inner receiver ended.
+    fn fill(&mut self, mode: RecvMode) -> Result<bool, FlowError> {
+        match mode.recv(&mut self.inner)? {
+            None => Ok(false),
+            Some((a, b)) => {
+                self.left.push_back(a);
+                self.right.push_back(b);
+                Ok(true)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub struct UnzipLeftRx<R, A, B> {
+    state: Arc<Mutex<UnzipState<R, A, B>>>,
+}
+
+#[cfg(feature = "std")]
+pub struct UnzipRightRx<R, A, B> {
+    state: Arc<Mutex<UnzipState<R, A, B>>>,
+}
+
+#[cfg(feature = "std")]
+pub fn unzip<R, A, B>(inner: R) -> (UnzipLeftRx<R, A, B>, UnzipRightRx<R, A, B>)
+where
+    R: Rx<Item = (A, B)>,
+{
+    let state = Arc::new(Mutex::new(UnzipState {
+        inner,
+        left: VecDeque::new(),
+        right: VecDeque::new(),
+    }));
+    (
+        UnzipLeftRx {
+            state: Arc::clone(&state),
+        },
+        UnzipRightRx { state },
+    )
+}
+
+#[cfg(feature = "std")]
+impl<R, A, B> Rx for UnzipLeftRx<R, A, B>
+where
+    R: Rx<Item = (A, B)>,
+{
+    type Item = A;
+
+    imp_recv_modes!(self, mode => {
+        let mut state = self.state.lock().unwrap();
+        if state.left.is_empty() && !state.fill(mode)? {
+            return Ok(None);
+        }
+        Ok(state.left.pop_front())
+    });
+}
+
+#[cfg(feature = "std")]
+impl<R, A, B> Rx for UnzipRightRx<R, A, B>
+where
+    R: Rx<Item = (A, B)>,
+{
+    type Item = B;
+
+    imp_recv_modes!(self, mode => {
+        let mut state = self.state.lock().unwrap();
+        if state.right.is_empty() && !state.fill(mode)? {
+            return Ok(None);
+        }
+        Ok(state.right.pop_front())
+    });
+}
+
+#[cfg(feature = "std")]
+struct SplitState<R, T> {
+    inner: R,
+    lanes: Vec<VecDeque<T>>,
+}
+
+/// One lane of a receiver of vectors. Vectors shorter than the number of lanes leave the
+/// remaining lanes without a value for that round, and extra elements are discarded.
+#[cfg(feature = "std")]
+pub struct SplitRx<R, T> {
+    state: Arc<Mutex<SplitState<R, T>>>,
+    lane: usize,
+}
+
+#[cfg(feature = "std")]
+pub fn split<R, T>(inner: R, lanes: usize) -> Vec<SplitRx<R, T>>
+where
+    R: Rx<Item = Vec<T>>,
+{
+    let state = Arc::new(Mutex::new(SplitState {
+        inner,
+        lanes: (0..lanes).map(|_| VecDeque::new()).collect(),
+    }));
+    (0..lanes)
+        .map(|lane| SplitRx {
+            state: Arc::clone(&state),
+            lane,
+        })
+        .collect()
+}
+
+#[cfg(feature = "std")]
+impl<R, T> Rx for SplitRx<R, T>
+where
+    R: Rx<Item = Vec<T>>,
+{
+    type Item = T;
+
+    imp_recv_modes!(self, mode => {
+        let mut state = self.state.lock().unwrap();
+        while state.lanes[self.lane].is_empty() {
+            match mode.recv(&mut state.inner)? {
+                None => return Ok(None),
+                Some(vec) => {
+                    for (queue, item) in state.lanes.iter_mut().zip(vec) {
+                        queue.push_back(item);
+                    }
+                }
+            }
+        }
+        Ok(state.lanes[self.lane].pop_front())
+    });
+}
+
+pub struct RxIteratorAdapter<R> {
+    inner: R,
+}
+
+impl<R> RxIteratorAdapter<R> {
+    pub fn new(inner: R) -> Self {
+        RxIteratorAdapter { inner }
+    }
+}
+
+impl<R: Rx> Iterator for RxIteratorAdapter<R> {
+    type Item = Result<R::Item, FlowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.recv() {
+            Err(e) => Some(Err(e)),
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => None,
+        }
+    }
+}
+
+pub struct IteratorRx<I> {
+    iter: I,
+}
+
+impl<I> IteratorRx<I> {
+    pub fn new(iter: I) -> Self {
+        IteratorRx { iter }
+    }
+}
+
+impl<I> Rx for IteratorRx<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        Ok(self.iter.next())
+    }
+}
+
+impl<I> From<I> for IteratorRx<I::IntoIter>
+where
+    I: IntoIterator,
+{
+    fn from(from: I) -> Self {
         Self::new(from.into_iter())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::format;
+    use alloc::string::ToString;
+    #[cfg(feature = "std")]
+    use std::sync::mpsc;
+
+    fn failing() -> impl Rx<Item = i32> {
+        IteratorRx::from(vec![Ok(1), Err("bad"), Ok(2), Err("worse")]).and_then(
+            |rx: &mut IteratorRx<_>| match rx.recv()? {
+                None => Ok(None),
+                Some(Ok(item)) => Ok(Some(item)),
+                Some(Err(e)) => Err(FlowError::from(e)),
+            },
+        )
+    }
+
+    #[test]
+    fn test_recover() {
+        let vec = failing().recover(|_| Some(0)).collect_vec().unwrap();
+        assert_eq!(vec, &[1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_skip_errors() {
+        let vec = failing().skip_errors().collect_vec().unwrap();
+        assert_eq!(vec, &[1, 2]);
+    }
+
+    #[test]
+    fn test_map_err() {
+        let mut rx = failing().map_err(|e| format!("wrapped: {}", e).into());
+        assert_eq!(rx.recv().unwrap(), Some(1));
+        assert_eq!(rx.recv().unwrap_err().to_string(), "wrapped: bad");
+    }
+
+    #[test]
+    fn test_errors_to() {
+        let mut errors = Vec::new();
+        let vec = failing()
+            .errors_to(VecCollectorTx::new(&mut errors))
+            .collect_vec()
+            .unwrap();
+        assert_eq!(vec, &[1, 2]);
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, &["bad", "worse"]);
+    }
+
+    #[test]
+    fn test_filter_map_take_skip() {
+        let vec = IteratorRx::from(0..20)
+            .filter(|i| i % 2 == 0)
+            .filter_map(|i| if i % 3 != 0 { Some(i * 10) } else { None })
+            .skip(1)
+            .take(3)
+            .collect_vec()
+            .unwrap();
+        assert_eq!(vec, &[40, 80, 100]);
+    }
+
+    #[test]
+    fn test_take_does_not_poll() {
+        let mut polled = 0;
+        {
+            let mut rx = CounterRx::new().inspect(|_| polled += 1).take(2);
+            assert_eq!(rx.recv().unwrap(), Some(0));
+            assert_eq!(rx.recv().unwrap(), Some(1));
+            assert_eq!(rx.recv().unwrap(), None);
+            assert_eq!(rx.recv().unwrap(), None);
+        }
+        assert_eq!(polled, 2);
+    }
+
+    #[test]
+    fn test_take_while_scan() {
+        let vec = CounterRx::new()
+            .take_while(|i| *i < 5)
+            .scan(0, |sum, i| {
+                *sum += i;
+                Some(*sum)
+            })
+            .collect_vec()
+            .unwrap();
+        assert_eq!(vec, &[0, 1, 3, 6, 10]);
+
+        let vec = IteratorRx::from(1..10)
+            .scan((), |_, i| if i < 3 { Some(i) } else { None })
+            .collect_vec()
+            .unwrap();
+        assert_eq!(vec, &[1, 2]);
+    }
+
+    #[test]
+    fn test_chain_enumerate() {
+        let vec = IteratorRx::from("ab".chars())
+            .chain(IteratorRx::from("c".chars()))
+            .enumerate()
+            .collect_vec()
+            .unwrap();
+        assert_eq!(vec, &[(0, 'a'), (1, 'b'), (2, 'c')]);
+    }
+
+    #[test]
+    fn test_window() {
+        let vec = IteratorRx::from(1..=5).window(3).collect_vec().unwrap();
+        assert_eq!(vec, &[vec![1, 2, 3], vec![2, 3, 4], vec![3, 4, 5]]);
+
+        let vec = IteratorRx::from(1..=2).window(3).collect_vec().unwrap();
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn test_batch() {
+        let vec = IteratorRx::from(1..=5).batch(2).collect_vec().unwrap();
+        assert_eq!(vec, &[vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn test_batch_error() {
+        let mut rx = failing().batch(2);
+        assert_eq!(rx.recv().unwrap_err().to_string(), "bad");
+        // value received before the error is kept
+        assert_eq!(rx.recv().unwrap(), Some(vec![1, 2]));
+        assert_eq!(rx.recv().unwrap_err().to_string(), "worse");
+        assert_eq!(rx.recv().unwrap(), None);
+    }
+
+    #[test]
+    fn test_zip() {
+        let zipped = IteratorRx::from(vec![1, 2, 3])
+            .zip(IteratorRx::from("ab".chars()))
+            .collect_vec()
+            .unwrap();
+        assert_eq!(zipped, &[(1, 'a'), (2, 'b')]);
+    }
+
+    #[test]
+    fn test_merge_round_robin() {
+        let merged = IteratorRx::from(vec![1, 2, 3, 4])
+            .merge(IteratorRx::from(vec![10, 20]))
+            .collect_vec()
+            .unwrap();
+        assert_eq!(merged, &[1, 10, 2, 20, 3, 4]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_poll_chain() {
+        let (mut tx, rx) = bounded(8, OverflowPolicy::Block);
+        let mut chain = rx.skip(1).filter(|x| x % 2 == 0).batch(2).enumerate();
+        assert!(matches!(chain.try_recv(), Err(FlowError::Empty)));
+        let start = Instant::now();
+        let timeout = Duration::from_millis(20);
+        assert!(matches!(
+            chain.recv_timeout(timeout),
+            Err(FlowError::Timeout)
+        ));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        tx.send_all(vec![0, 2, 3, 4, 6]).unwrap();
+        assert_eq!(chain.try_recv().unwrap(), Some((0, vec![2, 4])));
+        // 6 waits in the batch for the next value
+        assert!(matches!(chain.try_recv(), Err(FlowError::Empty)));
+        drop(tx);
+        assert_eq!(chain.try_recv().unwrap(), Some((1, vec![6])));
+        assert_eq!(chain.try_recv().unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_poll_zip() {
+        let (mut tx1, rx1) = bounded(2, OverflowPolicy::Block);
+        let (mut tx2, rx2) = bounded(2, OverflowPolicy::Block);
+        let mut zipped = rx1.zip(rx2).merge(IteratorRx::from(vec![(0, 'z')]));
+        assert_eq!(zipped.try_recv().unwrap(), Some((0, 'z')));
+        tx1.send(1).unwrap();
+        assert!(matches!(zipped.try_recv(), Err(FlowError::Empty)));
+        // the first value is kept until its pair arrives
+        tx2.send('a').unwrap();
+        assert_eq!(zipped.try_recv().unwrap(), Some((1, 'a')));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_merge_ready() {
+        let (mut tx1, rx1) = pair();
+        let (mut tx2, rx2) = pair();
+        let mut merged = rx1.merge_ready(rx2);
+
+        tx2.send(2).unwrap();
+        assert_eq!(merged.recv().unwrap(), Some(2));
+        tx1.send(1).unwrap();
+        assert_eq!(merged.recv().unwrap(), Some(1));
+        drop(tx1);
+        drop(tx2);
+        assert_eq!(merged.recv().unwrap(), None);
+
+        // sources blocked in `recv()` do not hold up the drop
+        let (_tx1, rx1) = pair::<i32>();
+        let (_tx2, rx2) = pair();
+        let mut merged = rx1.merge_ready(rx2);
+        assert!(matches!(merged.try_recv(), Err(FlowError::Empty)));
+        drop(merged);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_merge_ready_backpressure() {
+        // source takes a token before every value, and can only take it while it is not blocked
+        let (gate, tokens) = mpsc::sync_channel(0);
+        let gated = CounterRx::new().map(move |x| {
+            let _ = tokens.recv();
+            x
+        });
+        let (_tx, idle) = pair();
+        let merged = ReadyMergeRx::new();
+        let idle = idle.with_cancellation(merged.cancellation_token().clone());
+        let mut merged = merged.with(gated).with(idle);
+        // dropped before the merger, so that a failing assertion does not leave the source at it
+        let gate = gate;
+        // one value queued, and one waiting for room
+        gate.send(()).unwrap();
+        gate.send(()).unwrap();
+        assert!(gate.try_send(()).is_err());
+        assert_eq!(merged.recv().unwrap(), Some(0));
+        gate.send(()).unwrap();
+        assert!(gate.try_send(()).is_err());
+        assert_eq!(merged.recv().unwrap(), Some(1));
+        // stops the busy source on hang up, and the idle one by cancellation
+        drop(gate);
+        drop(merged);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_unzip() {
+        let (left, right) = IteratorRx::from(vec![(1, 'a'), (2, 'b')]).unzip();
+        assert_eq!(right.collect_vec().unwrap(), &['a', 'b']);
+        assert_eq!(left.collect_vec().unwrap(), &[1, 2]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_split() {
+        let mut lanes = IteratorRx::from(vec![vec![1, 10], vec![2, 20], vec![3]]).split(2);
+        let second = lanes.pop().unwrap();
+        let first = lanes.pop().unwrap();
+        assert_eq!(second.collect_vec().unwrap(), &[10, 20]);
+        assert_eq!(first.collect_vec().unwrap(), &[1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_cancel_on() {
+        let token = CancellationToken::new();
+        let mut rx = CounterRx::new().cancel_on(token.child_token());
+        assert_eq!(rx.recv().unwrap(), Some(0));
+        token.cancel();
+        assert!(rx.recv().unwrap_err().is_cancelled());
+
+        // blocking stage is interrupted too when it gets the token
+        let token = CancellationToken::new();
+        let (_sender, receiver) = pair::<i32>();
+        let mut rx = receiver
+            .with_cancellation(token.clone())
+            .map(|x| x + 1)
+            .cancel_on(token.clone());
+        let thread = std::thread::spawn(move || rx.recv());
+        token.cancel();
+        assert!(thread.join().unwrap().unwrap_err().is_cancelled());
+    }
+}
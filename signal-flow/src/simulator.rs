@@ -0,0 +1,461 @@
+//! Lossy channel simulation for robustness testing of protocols like RTSM.
+//!
+//! The simulated provider holds each value for one `period`, like a performance counter updated
+//! on a timer. The simulated consumer samples it at its own, unsynchronized rate, which may be
+//! skewed and jittered, and individual samples may be dropped, duplicated or corrupted. All
+//! randomness comes from a seeded generator, so runs are reproducible.
+use std::time::Duration;
+
+use super::*;
+
+/// Small deterministic pseudo-random generator (SplitMix64). Not suitable for cryptography.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        SimRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniformly distributed in `0..n`. `n` must not be zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// True with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+}
+
+/// Values which can be damaged in transit.
+pub trait Corrupt {
+    fn corrupt(&self, rng: &mut SimRng) -> Self;
+}
+
+impl Corrupt for bool {
+    fn corrupt(&self, _rng: &mut SimRng) -> Self {
+        !self
+    }
+}
+
+macro_rules! imp_corrupt {
+    ($($int:ty),*) => {
+        $(
+            /// Flips one random bit.
+            impl Corrupt for $int {
+                fn corrupt(&self, rng: &mut SimRng) -> Self {
+                    self ^ (1 << rng.below(<$int>::BITS as u64))
+                }
+            }
+        )*
+    };
+}
+
+imp_corrupt!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// Imperfections of the simulated channel. Default is a perfect channel which is only
+/// unsynchronized, i.e. samples once per period at a random phase.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelConditions {
+    /// Probability that a sample is lost.
+    pub drop_rate: f64,
+    /// Probability that a sample is delivered twice.
+    pub duplicate_rate: f64,
+    /// Probability that a sample is damaged with `Corrupt`.
+    pub corrupt_rate: f64,
+    /// Relative difference of the sampling interval from the period, e.g. `0.1` samples
+    /// 10% slower (losing values) and `-0.1` 10% faster (repeating values).
+    pub skew: f64,
+    /// Maximum deviation of each sampling instant, as a fraction of the sampling interval.
+    pub jitter: f64,
+}
+
+impl ChannelConditions {
+    /// Performance Monitor polling a counter with the same nominal interval as the provider
+    /// updates it: timers of two processes drift apart and fire with some jitter, while
+    /// samples themselves are never damaged.
+    pub fn performance_monitor() -> Self {
+        ChannelConditions {
+            skew: 0.02,
+            jitter: 0.2,
+            ..Default::default()
+        }
+    }
+}
+
+/// What happened to the samples so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// Number of sampling instants.
+    pub samples: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub corrupted: u64,
+}
+
+/// Fate of a single sample.
+enum Fate {
+    Dropped,
+    Delivered { corrupt: bool, duplicate: bool },
+}
+
+/// Sampling schedule shared by the Rx and Tx simulators.
+struct Channel<C> {
+    period: Duration,
+    conditions: ChannelConditions,
+    rng: SimRng,
+    clock: C,
+    phase: Duration,
+    /// Virtual time of the last sample since the start.
+    now: Duration,
+    /// Virtual time of the next sample, once planned.
+    next: Option<Duration>,
+    stats: ChannelStats,
+}
+
+impl<C: Clock> Channel<C> {
+    fn new(period: Duration, conditions: ChannelConditions, seed: u64, clock: C) -> Self {
+        assert!(!period.is_zero(), "Period must not be zero");
+        assert!(conditions.skew > -1.0, "Skew must be greater than -1");
+        let mut rng = SimRng::new(seed);
+        let phase = period.mul_f64(rng.next_f64());
+        Channel {
+            period,
+            conditions,
+            rng,
+            clock,
+            phase,
+            now: Duration::ZERO,
+            next: None,
+            stats: ChannelStats::default(),
+        }
+    }
+
+    /// Virtual time of the next sample.
+    fn peek(&mut self) -> Duration {
+        if let Some(next) = self.next {
+            return next;
+        }
+        let interval = self.period.mul_f64(1.0 + self.conditions.skew);
+        let nominal = self.phase + interval.mul_f64(self.stats.samples as f64);
+        let deviation = self.conditions.jitter * (2.0 * self.rng.next_f64() - 1.0);
+        let jitter = interval.mul_f64(deviation.abs());
+        let next = if deviation < 0.0 {
+            nominal.saturating_sub(jitter)
+        } else {
+            nominal + jitter
+        };
+        // jitter must not reorder samples
+        let next = next.max(self.now);
+        self.next = Some(next);
+        next
+    }
+
+    /// Index of the value which the next sample observes.
+    fn peek_index(&mut self) -> u64 {
+        (self.peek().as_nanos() / self.period.as_nanos()) as u64
+    }
+
+    /// Wait until the next sample is due, and decide what happens to it.
+    fn take(&mut self) -> Fate {
+        let next = self.peek();
+        self.next = None;
+        self.clock.sleep(next - self.now);
+        self.now = next;
+        self.stats.samples += 1;
+
+        if self.rng.chance(self.conditions.drop_rate) {
+            self.stats.dropped += 1;
+            return Fate::Dropped;
+        }
+        let corrupt = self.rng.chance(self.conditions.corrupt_rate);
+        let duplicate = self.rng.chance(self.conditions.duplicate_rate);
+        self.stats.corrupted += corrupt as u64;
+        self.stats.duplicated += duplicate as u64;
+        Fate::Delivered { corrupt, duplicate }
+    }
+
+    fn apply<T: Clone + Corrupt>(&mut self, value: &T, corrupt: bool) -> T {
+        if corrupt {
+            value.corrupt(&mut self.rng)
+        } else {
+            value.clone()
+        }
+    }
+}
+
+////////////////////////////////////////////////
+////////////////////// Rx //////////////////////
+////////////////////////////////////////////////
+
+/// Samples values of the inner Rx over a simulated lossy channel. Inner Rx is treated as the
+/// provider which yields a new value every `period`.
+pub struct ChannelSimulatorRx<R: Rx, C = VirtualClock> {
+    inner: R,
+    channel: Channel<C>,
+    /// Latest provider value, and its index.
+    current: Option<(R::Item, u64)>,
+    duplicate: Option<R::Item>,
+}
+
+impl<R: Rx> ChannelSimulatorRx<R> {
+    pub fn new(inner: R, period: Duration, conditions: ChannelConditions, seed: u64) -> Self {
+        ChannelSimulatorRx::with_clock(inner, period, conditions, seed, VirtualClock::new())
+    }
+}
+
+impl<R: Rx, C: Clock> ChannelSimulatorRx<R, C> {
+    /// Same as `new`, but waits for sampling instants on the given `clock`.
+    pub fn with_clock(
+        inner: R,
+        period: Duration,
+        conditions: ChannelConditions,
+        seed: u64,
+        clock: C,
+    ) -> Self {
+        ChannelSimulatorRx {
+            inner,
+            channel: Channel::new(period, conditions, seed, clock),
+            current: None,
+            duplicate: None,
+        }
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.channel.stats
+    }
+}
+
+impl<R, C> Rx for ChannelSimulatorRx<R, C>
+where
+    R: Rx,
+    R::Item: Clone + Corrupt,
+    C: Clock,
+{
+    type Item = R::Item;
+
+    imp_recv_modes!(self, mode => {
+        if let Some(value) = self.duplicate.take() {
+            return Ok(Some(value));
+        }
+        loop {
+            let index = self.channel.peek_index();
+            // skip provider values which were overwritten before the sample
+            while self.current.as_ref().is_none_or(|(_, i)| *i < index) {
+                let next = match &self.current {
+                    None => 0,
+                    Some((_, i)) => i + 1,
+                };
+                match mode.recv(&mut self.inner)? {
+                    None => return Ok(None),
+                    Some(value) => self.current = Some((value, next)),
+                }
+            }
+            let Fate::Delivered { corrupt, duplicate } = self.channel.take() else {
+                continue;
+            };
+            let (value, _) = self.current.as_ref().unwrap();
+            let value = self.channel.apply(value, corrupt);
+            if duplicate {
+                self.duplicate = Some(value.clone());
+            }
+            return Ok(Some(value));
+        }
+    });
+}
+
+////////////////////////////////////////////////
+////////////////////// Tx //////////////////////
+////////////////////////////////////////////////
+
+/// Delivers samples of sent values to the inner Tx over a simulated lossy channel. Every sent
+/// value is held for one `period`, during which it may be sampled any number of times.
+pub struct ChannelSimulatorTx<X, C = VirtualClock> {
+    inner: X,
+    channel: Channel<C>,
+    /// Index of the next sent value.
+    index: u64,
+}
+
+impl<X: Tx> ChannelSimulatorTx<X> {
+    pub fn new(inner: X, period: Duration, conditions: ChannelConditions, seed: u64) -> Self {
+        ChannelSimulatorTx::with_clock(inner, period, conditions, seed, VirtualClock::new())
+    }
+}
+
+impl<X: Tx, C: Clock> ChannelSimulatorTx<X, C> {
+    /// Same as `new`, but waits for sampling instants on the given `clock`.
+    pub fn with_clock(
+        inner: X,
+        period: Duration,
+        conditions: ChannelConditions,
+        seed: u64,
+        clock: C,
+    ) -> Self {
+        ChannelSimulatorTx {
+            inner,
+            channel: Channel::new(period, conditions, seed, clock),
+            index: 0,
+        }
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.channel.stats
+    }
+}
+
+impl<X, C> Tx for ChannelSimulatorTx<X, C>
+where
+    X: Tx,
+    X::Item: Clone + Corrupt,
+    C: Clock,
+{
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        while self.channel.peek_index() == self.index {
+            if let Fate::Delivered { corrupt, duplicate } = self.channel.take() {
+                let sample = self.channel.apply(&value, corrupt);
+                if duplicate {
+                    self.inner.send(sample.clone())?;
+                }
+                self.inner.send(sample)?;
+            }
+        }
+        self.index += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.inner.close()
+    }
+}
+
+/// Fraction of `expected` items which were lost, inserted or replaced in `actual`, measured as
+/// edit distance. Zero for an exact match.
+pub fn error_rate<T: PartialEq>(expected: &[T], actual: &[T]) -> f64 {
+    if expected.is_empty() {
+        return if actual.is_empty() { 0.0 } else { 1.0 };
+    }
+    let mut row: Vec<usize> = (0..=actual.len()).collect();
+    for (i, e) in expected.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, a) in actual.iter().enumerate() {
+            let substitution = diagonal + (e != a) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[actual.len()] as f64 / expected.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtsm::*;
+
+    const PERIOD: Duration = Duration::from_millis(100);
+
+    fn signal() -> Vec<Signal> {
+        let mut rng = SimRng::new(7);
+        (0..500).map(|_| rng.chance(0.5)).collect()
+    }
+
+    /// Send signal through RTSM over the simulated channel, and decode what comes out.
+    fn transmit(conditions: ChannelConditions, seed: u64) -> (Vec<Signal>, ChannelStats) {
+        let mut samples = Vec::new();
+        let mut channel = (&mut samples).simulate_channel(PERIOD, conditions, seed);
+        (&mut channel)
+            .rtsm(RtsmRanges::new(0..3, 50..53).unwrap())
+            .send_all(signal())
+            .unwrap();
+        let stats = channel.stats();
+        drop(channel);
+
+        let decoded = IteratorRx::from(samples)
+            .rtsm(RtsmRanges::new(0..3, 50..53).unwrap())
+            .skip_errors()
+            .collect_vec()
+            .unwrap();
+        (decoded, stats)
+    }
+
+    #[test]
+    fn test_perfect_channel() {
+        let (decoded, stats) = transmit(ChannelConditions::default(), 1);
+        assert_eq!(decoded, signal());
+        assert_eq!(stats.samples, 500);
+    }
+
+    #[test]
+    fn test_oversampling_survives_duplicates_and_jitter() {
+        let conditions = ChannelConditions {
+            duplicate_rate: 0.2,
+            skew: -0.4,
+            jitter: 0.3,
+            ..Default::default()
+        };
+        let (decoded, stats) = transmit(conditions, 2);
+        assert_eq!(decoded, signal());
+        assert!(stats.duplicated > 0);
+    }
+
+    #[test]
+    fn test_lossy_channel() {
+        let conditions = ChannelConditions {
+            drop_rate: 0.05,
+            corrupt_rate: 0.05,
+            ..ChannelConditions::performance_monitor()
+        };
+        let (decoded, stats) = transmit(conditions, 3);
+        let rate = error_rate(&signal(), &decoded);
+        assert!(rate > 0.0 && rate < 0.5, "error rate {}", rate);
+        assert!(stats.dropped > 0 && stats.corrupted > 0);
+        // same seed, same run
+        assert_eq!(transmit(conditions, 3).0, decoded);
+    }
+
+    #[test]
+    fn test_rx_undersampling() {
+        let clock = VirtualClock::new();
+        let conditions = ChannelConditions {
+            skew: 1.0,
+            ..Default::default()
+        };
+        let rx =
+            ChannelSimulatorRx::with_clock(CounterRx::new(), PERIOD, conditions, 4, clock.clone())
+                .take(5);
+        let samples = rx.collect_vec().unwrap();
+        // every other value is lost
+        assert!(samples.windows(2).all(|pair| pair[1] - pair[0] == 2));
+        assert!(clock.elapsed() >= 8 * PERIOD);
+    }
+
+    #[test]
+    fn test_error_rate() {
+        assert_eq!(error_rate(&[1, 2, 3, 4], &[1, 2, 3, 4]), 0.0);
+        assert_eq!(error_rate(&[1, 2, 3, 4], &[1, 3, 4]), 0.25);
+        assert_eq!(error_rate(&[1, 2, 3, 4], &[1, 2, 9, 3, 5]), 0.5);
+    }
+}
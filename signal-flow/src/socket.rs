@@ -0,0 +1,569 @@
+//! Tx/Rx pair connected over a TCP or Unix domain socket, for pipelines split across processes.
+//!
+//! Values are serialized with bincode into length-prefixed frames. Each frame starts with a
+//! kind byte followed by little-endian `u32` payload length. Orderly `close()` of the sender
+//! transmits an end-of-stream frame; a connection lost without one is treated as a temporary
+//! failure, and receiver waits for the sender to reconnect.
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::*;
+
+const FRAME_ITEM: u8 = 0;
+const FRAME_END: u8 = 1;
+
+/// Frames with longer payload are rejected as corrupted.
+pub const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// How often `SocketRx::recv_timeout()` checks for an incoming connection.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Where to listen or connect to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    /// TCP `host:port` address.
+    Tcp(String),
+    /// Path to a Unix domain socket.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// What sender should do when the connection is lost or could not be established.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReconnectPolicy {
+    /// Fail right away.
+    Never,
+    /// Try to connect again up to `attempts` times, waiting `delay` before each attempt.
+    Retry { attempts: u32, delay: Duration },
+}
+
+/// Received bytes do not form a valid frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    UnknownKind(u8),
+    TooLong(u32),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::UnknownKind(kind) => write!(f, "unknown frame kind {}", kind),
+            FrameError::TooLong(len) => write!(f, "frame of {} bytes is too long", len),
+        }
+    }
+}
+
+impl Error for FrameError {}
+
+impl From<FrameError> for FlowError {
+    fn from(e: FrameError) -> Self {
+        FlowError::protocol(e)
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(endpoint: &Endpoint) -> io::Result<Stream> {
+        match endpoint {
+            Endpoint::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                // signals are latency-sensitive and small
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Ok(Stream::Unix(UnixStream::connect(path)?)),
+        }
+    }
+
+    /// Make reads wait only as long as `mode` allows, then fail with `WouldBlock` or `TimedOut`.
+    fn set_read_mode(&self, mode: RecvMode) -> io::Result<()> {
+        let timeout = match mode {
+            RecvMode::Block => None,
+            RecvMode::Try => Some(Duration::ZERO),
+            RecvMode::Timeout(deadline) => Some(deadline.remaining()),
+        };
+        // zero read timeout is not allowed, non-blocking mode does the same
+        let nonblocking = timeout.is_some_and(|timeout| timeout.is_zero());
+        let timeout = timeout.filter(|_| !nonblocking);
+        match self {
+            Stream::Tcp(stream) => {
+                stream.set_nonblocking(nonblocking)?;
+                stream.set_read_timeout(timeout)
+            }
+            #[cfg(unix)]
+            Stream::Unix(stream) => {
+                stream.set_nonblocking(nonblocking)?;
+                stream.set_read_timeout(timeout)
+            }
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Write),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Write),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => Ok(Stream::Unix(listener.accept()?.0)),
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.set_nonblocking(nonblocking),
+        }
+    }
+}
+
+/// Error of a read which gave up waiting as `mode` told it to.
+fn not_ready(mode: RecvMode) -> FlowError {
+    match mode {
+        RecvMode::Try => FlowError::Empty,
+        _ => FlowError::Timeout,
+    }
+}
+
+fn is_would_block(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(5 + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+////////////////////////////////////////////////
+//////////////////// Sender ////////////////////
+////////////////////////////////////////////////
+
+/// Connecting half of the socket pair. Sends values to a `SocketRx` listening at the endpoint.
+///
+/// Dropping it without `close()` looks like a lost connection to the receiver.
+pub struct SocketTx<T> {
+    endpoint: Endpoint,
+    policy: ReconnectPolicy,
+    stream: Option<Stream>,
+    closed: bool,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T> SocketTx<T> {
+    /// Connect to `endpoint`, retrying according to `policy` if nobody listens there yet.
+    pub fn connect(endpoint: Endpoint, policy: ReconnectPolicy) -> Result<Self, FlowError> {
+        let mut tx = SocketTx {
+            endpoint,
+            policy,
+            stream: None,
+            closed: false,
+            _marker: Default::default(),
+        };
+        tx.reconnect()?;
+        Ok(tx)
+    }
+
+    fn reconnect(&mut self) -> Result<&mut Stream, FlowError> {
+        let mut result = Stream::connect(&self.endpoint);
+        if let ReconnectPolicy::Retry { attempts, delay } = self.policy {
+            for _ in 0..attempts {
+                if result.is_ok() {
+                    break;
+                }
+                thread::sleep(delay);
+                result = Stream::connect(&self.endpoint);
+            }
+        }
+        Ok(self.stream.insert(result?))
+    }
+
+    /// Write the whole frame, reconnecting once if the connection turns out to be lost.
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), FlowError> {
+        if self.closed {
+            return Err(FlowError::Disconnected);
+        }
+        if let Some(stream) = &mut self.stream {
+            match stream.write_all(frame) {
+                Ok(()) => return Ok(()),
+                Err(_) => self.stream = None,
+            }
+        }
+        let stream = self.reconnect()?;
+        if let Err(e) = stream.write_all(frame) {
+            self.stream = None;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Tx for SocketTx<T> {
+    type Item = T;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        let payload = bincode::serialize(&value).map_err(FlowError::protocol)?;
+        let len = u32::try_from(payload.len()).unwrap_or(u32::MAX);
+        if len > MAX_FRAME_LEN {
+            return Err(FrameError::TooLong(len).into());
+        }
+        self.write_frame(&frame(FRAME_ITEM, &payload))
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        match &mut self.stream {
+            Some(stream) => Ok(stream.flush()?),
+            None => Ok(()),
+        }
+    }
+
+    /// Signal end of stream to the receiver. Further sends fail with `FlowError::Disconnected`.
+    fn close(&mut self) -> Result<(), FlowError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.write_frame(&frame(FRAME_END, &[]))?;
+        self.closed = true;
+        if let Some(stream) = self.stream.take() {
+            stream.shutdown()?;
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Receiver ///////////////////
+////////////////////////////////////////////////
+
+/// Listening half of the socket pair.
+///
+/// Accepts a connection on the first `recv()`, and accepts a new one whenever the current
+/// connection is lost without the end-of-stream frame. Returns `Ok(None)` once the sender
+/// closes the stream.
+///
+/// `try_recv()` and `recv_timeout()` give up while waiting for a connection as well as in the
+/// middle of a frame. Bytes read so far are kept, and the next call carries on from there.
+pub struct SocketRx<T> {
+    listener: Listener,
+    stream: Option<Stream>,
+    /// Bytes of the frame which is being read.
+    pending: Vec<u8>,
+    ended: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SocketRx<T> {
+    /// Start listening at `endpoint`. Unix domain socket file must not exist yet.
+    pub fn bind(endpoint: &Endpoint) -> Result<Self, FlowError> {
+        let listener = match endpoint {
+            Endpoint::Tcp(address) => Listener::Tcp(TcpListener::bind(address)?),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Listener::Unix(UnixListener::bind(path)?),
+        };
+        Ok(SocketRx {
+            listener,
+            stream: None,
+            pending: Vec::new(),
+            ended: false,
+            _marker: Default::default(),
+        })
+    }
+
+    /// Actual endpoint this receiver listens at, e.g. to find out port assigned by the OS.
+    pub fn local_endpoint(&self) -> Result<Endpoint, FlowError> {
+        match &self.listener {
+            Listener::Tcp(listener) => Ok(Endpoint::Tcp(listener.local_addr()?.to_string())),
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let address = listener.local_addr()?;
+                let path = address.as_pathname().ok_or("unnamed Unix domain socket")?;
+                Ok(Endpoint::Unix(path.to_owned()))
+            }
+        }
+    }
+
+    fn accept(&self, mode: RecvMode) -> Result<Stream, FlowError> {
+        self.listener
+            .set_nonblocking(!matches!(mode, RecvMode::Block))?;
+        loop {
+            match self.listener.accept() {
+                Ok(stream) => return Ok(stream),
+                Err(e) if is_would_block(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+            // there is no accept timeout, so poll until the deadline
+            match mode {
+                RecvMode::Timeout(deadline) if !deadline.remaining().is_zero() => {
+                    thread::sleep(deadline.remaining().min(ACCEPT_POLL_INTERVAL));
+                }
+                _ => return Err(not_ready(mode)),
+            }
+        }
+    }
+
+    /// Read into `pending` until it holds `len` bytes. `Ok(false)` if the connection has been
+    /// lost.
+    fn fill(
+        stream: &mut Stream,
+        pending: &mut Vec<u8>,
+        len: usize,
+        mode: RecvMode,
+    ) -> Result<bool, FlowError> {
+        while pending.len() < len {
+            stream.set_read_mode(mode)?;
+            let start = pending.len();
+            pending.resize(len, 0);
+            let read = stream.read(&mut pending[start..]);
+            pending.truncate(start + read.as_ref().map_or(0, |n| *n));
+            match read {
+                Ok(0) => return Ok(false),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if is_would_block(&e) => return Err(not_ready(mode)),
+                Err(_) => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read next frame, or `None` if the connection has been lost.
+    fn read_frame(
+        stream: &mut Stream,
+        pending: &mut Vec<u8>,
+        mode: RecvMode,
+    ) -> Result<Option<(u8, Vec<u8>)>, FlowError> {
+        if !Self::fill(stream, pending, 5, mode)? {
+            return Ok(None);
+        }
+        let kind = pending[0];
+        if kind != FRAME_ITEM && kind != FRAME_END {
+            return Err(FrameError::UnknownKind(kind).into());
+        }
+        let len = u32::from_le_bytes([pending[1], pending[2], pending[3], pending[4]]);
+        if len > MAX_FRAME_LEN {
+            return Err(FrameError::TooLong(len).into());
+        }
+        if !Self::fill(stream, pending, 5 + len as usize, mode)? {
+            return Ok(None);
+        }
+        let payload = pending.split_off(5);
+        pending.clear();
+        Ok(Some((kind, payload)))
+    }
+}
+
+impl<T: DeserializeOwned> Rx for SocketRx<T> {
+    type Item = T;
+
+    imp_recv_modes!(self, mode => {
+        while !self.ended {
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => self.stream.insert(self.accept(mode)?),
+            };
+            match SocketRx::<T>::read_frame(stream, &mut self.pending, mode) {
+                Ok(Some((FRAME_ITEM, payload))) => {
+                    let item = bincode::deserialize(&payload).map_err(FlowError::protocol)?;
+                    return Ok(Some(item));
+                }
+                Ok(Some(_)) => {
+                    self.ended = true;
+                    self.stream = None;
+                }
+                // wait for the sender to reconnect
+                Ok(None) => {
+                    self.stream = None;
+                    self.pending.clear();
+                }
+                // keep what was read of the frame for the next call
+                Err(e) if e.is_not_ready() => return Err(e),
+                Err(e) => {
+                    // stream is out of sync, there is no way to find the next frame
+                    self.stream = None;
+                    self.pending.clear();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(None)
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtsm::*;
+
+    fn local() -> SocketRx<u32> {
+        SocketRx::bind(&Endpoint::Tcp("127.0.0.1:0".to_owned())).unwrap()
+    }
+
+    #[test]
+    fn test_tcp_into_rtsm() {
+        let rx = local();
+        let endpoint = rx.local_endpoint().unwrap();
+        let provider = thread::spawn(move || {
+            let mut tx = SocketTx::connect(endpoint, ReconnectPolicy::Never).unwrap();
+            tx.send_all(vec![50u32, 0, 50, 0, 1, 50, 51]).unwrap();
+            tx.close().unwrap();
+            assert!(tx.send(1).unwrap_err().is_disconnected());
+        });
+
+        let signal = rx
+            .rtsm(RtsmRanges::new(0..3, 50..52).unwrap())
+            .collect_vec()
+            .unwrap();
+        assert_eq!(signal, &[ON, OFF, ON, OFF, OFF, ON, ON]);
+        provider.join().unwrap();
+    }
+
+    #[test]
+    fn test_rx_accepts_reconnect() {
+        let rx = local();
+        let endpoint = rx.local_endpoint().unwrap();
+        let provider = thread::spawn(move || {
+            let mut tx = SocketTx::connect(endpoint.clone(), ReconnectPolicy::Never).unwrap();
+            tx.send_all(vec![1, 2]).unwrap();
+            // connection lost without end of stream
+            drop(tx);
+            let mut tx = SocketTx::connect(endpoint, ReconnectPolicy::Never).unwrap();
+            tx.send_all(vec![3]).unwrap();
+            tx.close().unwrap();
+        });
+
+        assert_eq!(rx.collect_vec().unwrap(), &[1, 2, 3]);
+        provider.join().unwrap();
+    }
+
+    #[test]
+    fn test_bad_frame() {
+        let mut rx = local();
+        let Endpoint::Tcp(address) = rx.local_endpoint().unwrap() else {
+            unreachable!()
+        };
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(&frame(7, &[])).unwrap();
+        let err = rx.recv().unwrap_err();
+        assert_eq!(
+            err.protocol_error::<FrameError>(),
+            Some(&FrameError::UnknownKind(7))
+        );
+    }
+
+    #[test]
+    fn test_rx_does_not_block() {
+        let mut rx = local();
+        assert!(matches!(rx.try_recv(), Err(FlowError::Empty)));
+        let timeout = rx.recv_timeout(Duration::from_millis(20));
+        assert!(matches!(timeout, Err(FlowError::Timeout)));
+
+        let Endpoint::Tcp(address) = rx.local_endpoint().unwrap() else {
+            unreachable!()
+        };
+        let mut stream = TcpStream::connect(address).unwrap();
+        let item = frame(FRAME_ITEM, &bincode::serialize(&42u32).unwrap());
+        // half a frame is kept until the rest of it arrives
+        stream.write_all(&item[..3]).unwrap();
+        let timeout = rx.recv_timeout(Duration::from_millis(20));
+        assert!(matches!(timeout, Err(FlowError::Timeout)));
+        stream.write_all(&item[3..]).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(10)).unwrap(), Some(42));
+        assert!(matches!(rx.try_recv(), Err(FlowError::Empty)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_tx_retries_connect() {
+        let path = std::env::temp_dir().join(format!("signal-flow-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let endpoint = Endpoint::Unix(path.clone());
+
+        let provider = {
+            let endpoint = endpoint.clone();
+            thread::spawn(move || {
+                let policy = ReconnectPolicy::Retry {
+                    attempts: 100,
+                    delay: Duration::from_millis(10),
+                };
+                let mut tx = SocketTx::connect(endpoint, policy).unwrap();
+                tx.send_all("SOS".chars()).unwrap();
+                tx.close().unwrap();
+            })
+        };
+        // receiver comes up later than the sender
+        thread::sleep(Duration::from_millis(50));
+        let rx = SocketRx::<char>::bind(&endpoint).unwrap();
+        assert_eq!(rx.collect::<String>().unwrap(), "SOS");
+        provider.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
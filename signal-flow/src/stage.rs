@@ -0,0 +1,473 @@
+//! Running parts of a pipeline on their own threads, connected to the rest through pairs.
+//!
+//! Stage threads are supervised: panics are caught and reported by `join()`, and a stage may be
+//! rebuilt from its factory according to `RestartPolicy`. Dropping a handle cancels the stage
+//! and waits for its thread, except for `SpawnedTx`, which lets the stage send queued values
+//! first.
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::*;
+
+/// What to do when a stage fails, i.e. panics or returns an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Let the thread finish, and report the failure from `join()`.
+    Never,
+    /// Rebuild the stage up to `max_restarts` times, waiting `delay` before each restart.
+    Restart { max_restarts: u32, delay: Duration },
+}
+
+/// Why a stage thread has finished abnormally.
+#[derive(Debug)]
+pub enum StageError {
+    /// Stage panicked with the given message.
+    Panicked(String),
+    Failed(FlowError),
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StageError::Panicked(message) => write!(f, "stage panicked: {}", message),
+            StageError::Failed(e) => write!(f, "stage failed: {}", e),
+        }
+    }
+}
+
+impl Error for StageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StageError::Panicked(_) => None,
+            StageError::Failed(e) => Some(e),
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic payload".to_owned(),
+        },
+    }
+}
+
+/// Handle of a supervised thread. Cancels the thread and waits for it when dropped.
+pub struct StageHandle {
+    thread: Option<JoinHandle<Result<(), StageError>>>,
+    cancellation_token: CancellationToken,
+    restarts: Arc<AtomicU32>,
+}
+
+/// Run `f` on a new thread, calling it again whenever it fails, according to `policy`.
+///
+/// `f` gets a token which is cancelled by `StageHandle::cancel()`, and is expected to return
+/// soon after that. `Ok(())` or `FlowError::Cancelled` finish the stage normally.
+pub fn spawn_supervised<F>(policy: RestartPolicy, mut f: F) -> StageHandle
+where
+    F: FnMut(&CancellationToken) -> Result<(), FlowError> + Send + 'static,
+{
+    let cancellation_token = CancellationToken::new();
+    let restarts = Arc::new(AtomicU32::new(0));
+    let thread = {
+        let token = cancellation_token.clone();
+        let restarts = Arc::clone(&restarts);
+        thread::spawn(move || {
+            loop {
+                let error = match panic::catch_unwind(AssertUnwindSafe(|| f(&token))) {
+                    Ok(Ok(())) => return Ok(()),
+                    Ok(Err(e)) if e.is_cancelled() => return Ok(()),
+                    Ok(Err(e)) => StageError::Failed(e),
+                    Err(panic) => StageError::Panicked(panic_message(panic)),
+                };
+                let RestartPolicy::Restart {
+                    max_restarts,
+                    delay,
+                } = policy
+                else {
+                    return Err(error);
+                };
+                if restarts.load(Ordering::Relaxed) >= max_restarts || token.wait_timeout(delay) {
+                    return Err(error);
+                }
+                restarts.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    };
+    StageHandle {
+        thread: Some(thread),
+        cancellation_token,
+        restarts,
+    }
+}
+
+impl StageHandle {
+    /// Ask the stage to stop. Does not wait for it.
+    pub fn cancel(&self) {
+        self.cancellation_token.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// How many times the stage has been restarted after a failure.
+    pub fn restarts(&self) -> u32 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Cancel the stage and wait for its thread to finish.
+    pub fn join(mut self) -> Result<(), StageError> {
+        self.cancel();
+        self.wait()
+    }
+
+    fn wait(&mut self) -> Result<(), StageError> {
+        match self.thread.take() {
+            // panics are caught inside, so the thread itself never panics
+            Some(thread) => thread.join().unwrap_or(Ok(())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for StageHandle {
+    fn drop(&mut self) {
+        self.cancel();
+        let _ = self.wait();
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Rx stage ///////////////////
+////////////////////////////////////////////////
+
+/// How many values an Rx stage may receive ahead of its consumer.
+const STAGE_CAPACITY: usize = 16;
+
+/// Receives values of an Rx chain which runs on its own thread. Errors of the chain are passed
+/// through, and do not count as failures.
+///
+/// Stage receives at most `STAGE_CAPACITY` values ahead, then waits for them to be taken.
+pub struct SpawnedRx<T> {
+    // dropped before the handle, so that the stage is not left waiting for room
+    receiver: BoundedReceiverRx<Result<T, FlowError>>,
+    handle: StageHandle,
+}
+
+/// Build an Rx chain on a new thread with `factory`, and rebuild it whenever it panics
+/// according to `policy`. Chain is polled until it ends or the stage is cancelled.
+pub fn spawn_rx_stage<R, F>(policy: RestartPolicy, mut factory: F) -> SpawnedRx<R::Item>
+where
+    R: Rx,
+    R::Item: Send + 'static,
+    F: FnMut(&CancellationToken) -> R + Send + 'static,
+{
+    let (mut sender, receiver) = bounded(STAGE_CAPACITY, OverflowPolicy::Block);
+    let handle = spawn_supervised(policy, move |token| {
+        let mut rx = factory(token);
+        while !token.is_cancelled() {
+            let item = match rx.recv() {
+                Ok(None) => break,
+                Err(e) if e.is_cancelled() => break,
+                Ok(Some(item)) => Ok(item),
+                Err(e) => Err(e),
+            };
+            if sender.send(item).is_err() {
+                // nobody listens anymore
+                break;
+            }
+        }
+        Ok(())
+    });
+    SpawnedRx { receiver, handle }
+}
+
+impl<T> SpawnedRx<T> {
+    pub fn handle(&self) -> &StageHandle {
+        &self.handle
+    }
+
+    /// Cancel the stage and wait for its thread to finish. Values which were not received yet
+    /// are discarded.
+    pub fn join(self) -> Result<(), StageError> {
+        let SpawnedRx { receiver, handle } = self;
+        drop(receiver);
+        handle.join()
+    }
+}
+
+impl<T> Rx for SpawnedRx<T> {
+    type Item = T;
+
+    fn recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.receiver.recv()?.transpose()
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Self::Item>, FlowError> {
+        self.receiver.try_recv()?.transpose()
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Self::Item>, FlowError> {
+        self.receiver.recv_timeout(timeout)?.transpose()
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Tx stage ///////////////////
+////////////////////////////////////////////////
+
+/// Sends values to a Tx chain which runs on its own thread.
+///
+/// Sending only fails once the stage has finished. Failures of the chain are reported by
+/// `join()` and `close()`. Dropping it hangs up, and waits for the stage to process queued
+/// values, same as `join()`.
+pub struct SpawnedTx<T> {
+    sender: Option<SenderTx<T>>,
+    handle: StageHandle,
+}
+
+/// Build a Tx chain on a new thread with `factory`, and feed it with values sent to the
+/// returned `SpawnedTx`. Chain is closed when the input hangs up or the stage is cancelled.
+/// If the chain fails, it is rebuilt according to `policy`, and the failed value is lost.
+pub fn spawn_tx_stage<X, F>(policy: RestartPolicy, mut factory: F) -> SpawnedTx<X::Item>
+where
+    X: Tx,
+    X::Item: Send + 'static,
+    F: FnMut(&CancellationToken) -> X + Send + 'static,
+{
+    let (sender, receiver) = pair();
+    let mut receiver = Some(receiver);
+    let mut input = None;
+    let handle = spawn_supervised(policy, move |token| {
+        let input = input.get_or_insert_with(|| {
+            let receiver = receiver.take().unwrap();
+            receiver.with_cancellation(token.clone())
+        });
+        let mut tx = factory(token);
+        let sent = loop {
+            match input.recv() {
+                Ok(Some(value)) => {
+                    if let Err(e) = tx.send(value) {
+                        break Err(e);
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        let closed = tx.close();
+        sent.and(closed)
+    });
+    SpawnedTx {
+        sender: Some(sender),
+        handle,
+    }
+}
+
+impl<T> SpawnedTx<T> {
+    pub fn handle(&self) -> &StageHandle {
+        &self.handle
+    }
+
+    /// Hang up, and wait for the stage to process queued values and close its chain.
+    pub fn join(mut self) -> Result<(), StageError> {
+        self.hang_up()
+    }
+
+    fn hang_up(&mut self) -> Result<(), StageError> {
+        self.sender = None;
+        self.handle.wait()
+    }
+}
+
+impl<T> Tx for SpawnedTx<T> {
+    type Item = T;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        match &mut self.sender {
+            Some(sender) => sender.send(value),
+            None => Err(FlowError::Disconnected),
+        }
+    }
+
+    /// Same as `join()`, but keeps the failure of the chain as is, if it failed with an error.
+    fn close(&mut self) -> Result<(), FlowError> {
+        match self.hang_up() {
+            Ok(()) => Ok(()),
+            Err(StageError::Failed(e)) => Err(e),
+            Err(e) => Err(FlowError::other(e)),
+        }
+    }
+}
+
+impl<T> Drop for SpawnedTx<T> {
+    fn drop(&mut self) {
+        let _ = self.hang_up();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Mutex, mpsc};
+
+    #[test]
+    fn test_rx_stage() {
+        let mut rx = IteratorRx::from(vec![1, 2, 3])
+            .map(|x| x * 10)
+            .spawn_stage();
+        assert_eq!(rx.recv().unwrap(), Some(10));
+        assert_eq!(rx.collect_vec().unwrap(), &[20, 30]);
+
+        // infinite stage is stopped by join
+        let mut rx = CounterRx::new()
+            .interval(Duration::from_millis(1))
+            .spawn_stage();
+        assert_eq!(rx.recv().unwrap(), Some(0));
+        rx.join().unwrap();
+    }
+
+    #[test]
+    fn test_tx_stage() {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let mut tx = {
+            let out = Arc::clone(&out);
+            CustomTx::new(move |chunk: Vec<i32>| {
+                out.lock().unwrap().push(chunk);
+                Ok(())
+            })
+            .chunks(2)
+            .spawn_stage()
+        };
+        tx.send_all(vec![1, 2, 3]).unwrap();
+        // the chain is closed, so the partial chunk comes through
+        tx.join().unwrap();
+        assert_eq!(*out.lock().unwrap(), &[vec![1, 2], vec![3]]);
+    }
+
+    fn slow_stage(out: &Arc<Mutex<Vec<i32>>>) -> SpawnedTx<i32> {
+        let out = Arc::clone(out);
+        CustomTx::new(move |value| {
+            thread::sleep(Duration::from_millis(1));
+            out.lock().unwrap().push(value);
+            Ok(())
+        })
+        .spawn_stage()
+    }
+
+    #[test]
+    fn test_tx_stage_drains() {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let mut tx = slow_stage(&out);
+        tx.send_all(0..20).unwrap();
+        tx.close().unwrap();
+        assert_eq!(*out.lock().unwrap(), (0..20).collect::<Vec<_>>());
+        assert!(tx.send(20).unwrap_err().is_disconnected());
+
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let mut tx = slow_stage(&out);
+        tx.send_all(0..20).unwrap();
+        drop(tx);
+        assert_eq!(*out.lock().unwrap(), (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rx_stage_backpressure() {
+        // source takes a token before every value, and can only take it while it is not blocked
+        let (gate, tokens) = mpsc::sync_channel(0);
+        let mut rx = CounterRx::new()
+            .map(move |x| {
+                let _ = tokens.recv();
+                x
+            })
+            .spawn_stage();
+        // dropped before the stage, so that a failing assertion does not leave the source at it
+        let gate = gate;
+        // the queue is full, plus the value which waits for room
+        for _ in 0..STAGE_CAPACITY + 1 {
+            gate.send(()).unwrap();
+        }
+        assert!(gate.try_send(()).is_err());
+        assert_eq!(rx.recv().unwrap(), Some(0));
+        gate.send(()).unwrap();
+        assert!(gate.try_send(()).is_err());
+        drop(gate);
+        rx.join().unwrap();
+    }
+
+    #[test]
+    fn test_restart_on_panic() {
+        let builds = Arc::new(AtomicU32::new(0));
+        let policy = RestartPolicy::Restart {
+            max_restarts: 2,
+            delay: Duration::ZERO,
+        };
+        let mut rx = {
+            let builds = Arc::clone(&builds);
+            spawn_rx_stage(policy, move |_| {
+                let build = builds.fetch_add(1, Ordering::Relaxed);
+                IteratorRx::from(vec![build]).map(|build| {
+                    if build < 1 {
+                        panic!("build {} is broken", build);
+                    }
+                    build
+                })
+            })
+        };
+        assert_eq!(rx.recv().unwrap(), Some(1));
+        assert_eq!(rx.recv().unwrap(), None);
+        assert_eq!(rx.handle().restarts(), 1);
+        rx.join().unwrap();
+
+        let handle = spawn_supervised(RestartPolicy::Never, |_| panic!("oops"));
+        match handle.join() {
+            Err(StageError::Panicked(message)) => assert_eq!(message, "oops"),
+            other => panic!("expected panic, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_restart_on_error() {
+        let policy = RestartPolicy::Restart {
+            max_restarts: 1,
+            delay: Duration::ZERO,
+        };
+        let builds = Arc::new(AtomicU32::new(0));
+        let mut tx = {
+            let builds = Arc::clone(&builds);
+            spawn_tx_stage(policy, move |_| {
+                builds.fetch_add(1, Ordering::Relaxed);
+                CustomTx::new(|value: i32| {
+                    if value < 0 {
+                        Err(FlowError::from("negative"))
+                    } else {
+                        Ok(())
+                    }
+                })
+            })
+        };
+        tx.send_all(vec![1, -1, 2, -2]).unwrap();
+        let err = tx.join().unwrap_err();
+        assert!(matches!(err, StageError::Failed(FlowError::Other(_))));
+        assert_eq!(builds.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_cancel_supervised() {
+        let handle = spawn_supervised(RestartPolicy::Never, |token| {
+            token.wait();
+            Err(FlowError::Cancelled)
+        });
+        assert!(!handle.is_finished());
+        handle.join().unwrap();
+    }
+}
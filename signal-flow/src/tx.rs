@@ -1,9 +1,6 @@
-use std::error::Error;
-use std::fmt;
-use std::marker::PhantomData;
-use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
-use std::time::Duration;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use core::time::Duration;
 
 use super::*;
 
@@ -11,8 +8,33 @@ pub trait Tx {
     type Item;
 
     /// Blocking send value.
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>>;
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError>;
+
+    /// Non-blocking send value. Fails with `FlowError::Full` if the value can not be accepted right
+    /// now.
+    ///
+    /// Default implementation falls back to blocking `send`, which is the right thing for
+    /// senders which never block anyway.
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.send(value)
+    }
+
+    /// Write out values buffered by this Tx and its inner ones.
+    ///
+    /// Default implementation has nothing to write. Adapters forward it to their inner Tx.
+    fn flush(&mut self) -> Result<(), FlowError> {
+        Ok(())
+    }
+
+    /// Flush and release this Tx and its inner ones. Nothing should be sent after closing.
+    ///
+    /// Adapters close their inner Tx even if their own flush fails, so that resources down the
+    /// chain are released anyway. Default implementation just flushes.
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.flush()
+    }
 
+    #[cfg(feature = "std")]
     fn interval(self, rate: Duration) -> Interval<Self, IntervalRoleTx>
     where
         Self: Sized,
@@ -20,7 +42,34 @@ pub trait Tx {
         Interval::new(self, rate)
     }
 
-    fn cancel_on(self, cancellation_token: Arc<AtomicBool>) -> CancellableTx<Self>
+    /// Drift-free interval with ticks anchored at `start + n * rate`.
+    #[cfg(feature = "std")]
+    fn interval_fixed(
+        self,
+        rate: Duration,
+        policy: MissedTickPolicy,
+    ) -> Interval<Self, IntervalRoleTx>
+    where
+        Self: Sized,
+    {
+        Interval::new(self, rate).with_schedule(Schedule::Fixed(policy))
+    }
+
+    /// Same as `interval`, but measures time with the given `clock`.
+    #[cfg(feature = "std")]
+    fn interval_with_clock<C: Clock>(
+        self,
+        rate: Duration,
+        clock: C,
+    ) -> Interval<Self, IntervalRoleTx, C>
+    where
+        Self: Sized,
+    {
+        Interval::with_clock(self, rate, clock)
+    }
+
+    #[cfg(feature = "std")]
+    fn cancel_on(self, cancellation_token: CancellationToken) -> CancellableTx<Self>
     where
         Self: Sized,
     {
@@ -33,6 +82,149 @@ pub trait Tx {
     {
         ChunksTx::new(chunk_size, self)
     }
+
+    /// Encode bits with error correcting `code`. See `FecCode`.
+    fn fec_encode(self, code: FecCode) -> FecEncodeTx<Self>
+    where
+        Self: Sized + Tx<Item = bool>,
+    {
+        FecEncodeTx::new(code, self)
+    }
+
+    /// Pack values into checked frames of up to `max_len` values, and send them bit by bit. See
+    /// `FrameTx`.
+    fn frame<U>(self, max_len: usize) -> FrameTx<Self, U>
+    where
+        Self: Sized + Tx<Item = bool>,
+        U: FrameUnit,
+    {
+        FrameTx::new(max_len, self)
+    }
+
+    /// Convert values with `f` before sending them to this Tx (contramap).
+    fn with<U, F>(self, f: F) -> WithTx<Self, F, U>
+    where
+        Self: Sized,
+        F: FnMut(U) -> Self::Item,
+    {
+        WithTx::new(self, f)
+    }
+
+    /// Silently drop values which do not satisfy `predicate`.
+    fn filter<P>(self, predicate: P) -> FilterTx<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        FilterTx::new(self, predicate)
+    }
+
+    /// Call `f` with a reference to every value before sending it.
+    fn inspect<F>(self, f: F) -> InspectTx<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Item),
+    {
+        InspectTx::new(self, f)
+    }
+
+    /// Drop values equal to the last successfully sent one.
+    fn dedup(self) -> DedupTx<Self>
+    where
+        Self: Sized,
+        Self::Item: Clone + Eq,
+    {
+        DedupTx::new(self)
+    }
+
+    fn map_err<F>(self, f: F) -> MapErrTx<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(FlowError) -> FlowError,
+    {
+        MapErrTx::new(self, f)
+    }
+
+    /// Send a copy of every value to `other` as well.
+    fn tee<Y>(self, other: Y) -> TeeTx<Self, Y>
+    where
+        Self: Sized,
+        Y: Tx<Item = Self::Item>,
+    {
+        TeeTx::new(self, other)
+    }
+
+    /// Move this chain to its own thread, fed through a pair. See `spawn_tx_stage` for
+    /// restarting on failures.
+    #[cfg(feature = "std")]
+    fn spawn_stage(self) -> SpawnedTx<Self::Item>
+    where
+        Self: Sized + Send + 'static,
+        Self::Item: Send + 'static,
+    {
+        let mut tx = Some(self);
+        spawn_tx_stage(RestartPolicy::Never, move |_| {
+            tx.take().expect("stage is never restarted")
+        })
+    }
+
+    /// Deliver values over a simulated lossy channel, holding each for one `period`. See
+    /// `ChannelSimulatorTx`.
+    #[cfg(feature = "std")]
+    fn simulate_channel(
+        self,
+        period: Duration,
+        conditions: ChannelConditions,
+        seed: u64,
+    ) -> ChannelSimulatorTx<Self>
+    where
+        Self: Sized,
+    {
+        ChannelSimulatorTx::new(self, period, conditions, seed)
+    }
+
+    /// Record statistics of this stage into the global `MetricsRegistry` under `name`.
+    #[cfg(feature = "std")]
+    fn instrument(self, name: &str) -> InstrumentedTx<Self>
+    where
+        Self: Sized,
+    {
+        InstrumentedTx::new(self, MetricsRegistry::global().register(name))
+    }
+
+    /// Same as `instrument`, but records into the given `registry`.
+    #[cfg(feature = "std")]
+    fn instrument_in<C: Clock + Clone>(
+        self,
+        name: &str,
+        registry: &MetricsRegistry<C>,
+    ) -> InstrumentedTx<Self, C>
+    where
+        Self: Sized,
+    {
+        InstrumentedTx::new(self, registry.register(name))
+    }
+}
+
+/// Lend a Tx to a pipeline without giving up its ownership.
+impl<X: Tx + ?Sized> Tx for &mut X {
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        (**self).send(value)
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        (**self).try_send(value)
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        (**self).flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        (**self).close()
+    }
 }
 
 ////////////////////////////////////////////////
@@ -43,7 +235,7 @@ pub trait TxExt<Iter>: Tx
 where
     Iter: IntoIterator<Item = Self::Item>,
 {
-    fn send_all(&mut self, values: Iter) -> Result<(), Box<dyn Error>> {
+    fn send_all(&mut self, values: Iter) -> Result<(), FlowError> {
         for value in values.into_iter() {
             self.send(value)?;
         }
@@ -62,13 +254,32 @@ where
 //////////////////// Interval ////////////////////
 //////////////////////////////////////////////////
 
-impl<T: Tx> Tx for Interval<T, IntervalRoleTx> {
+#[cfg(feature = "std")]
+impl<T: Tx, C: Clock> Tx for Interval<T, IntervalRoleTx, C> {
     type Item = T::Item;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
-        self.sleep_and_update_last_call_time();
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.wait_for_tick()?;
         self.inner.send(value)
     }
+
+    /// Fails with `FlowError::Full` if the next tick is not due yet.
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if !self.is_tick_due() {
+            return Err(FlowError::Full);
+        }
+        self.wait_for_tick()?;
+        self.inner.try_send(value)
+    }
+
+    /// Flushing does not wait for a tick, but whatever the inner Tx sends does.
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.inner.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.inner.close()
+    }
 }
 
 //////////////////////////////////////////////
@@ -90,7 +301,7 @@ impl<T> NullTx<T> {
 impl<T> Tx for NullTx<T> {
     type Item = T;
 
-    fn send(&mut self, _value: Self::Item) -> Result<(), Box<dyn Error>> {
+    fn send(&mut self, _value: Self::Item) -> Result<(), FlowError> {
         Ok(())
     }
 }
@@ -112,29 +323,40 @@ impl<'a, T> VecCollectorTx<'a, T> {
 impl<'a, T> Tx for VecCollectorTx<'a, T> {
     type Item = T;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
         self.buffer.push(value);
         Ok(())
     }
 }
 
+/// Owned counterpart of `VecCollectorTx`.
+impl<T> Tx for Vec<T> {
+    type Item = T;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.push(value);
+        Ok(())
+    }
+}
+
 ////////////////////////////////////////////////
 ///////////////// Cancellable //////////////////
 ////////////////////////////////////////////////
 
-/// Passes values through unless `cancellation_token` (AtomicBool) is set to true,
-/// in which case it returns an error.
+/// Passes values through unless `cancellation_token` is cancelled, in which case it returns
+/// `FlowError::Cancelled`.
+///
+/// Cancellation only rejects new values: `flush` and `close` are always forwarded, so that
+/// whatever is buffered down the chain can still be written out on shutdown.
+#[cfg(feature = "std")]
 pub struct CancellableTx<X> {
     tx: X,
-    cancellation_token: Arc<AtomicBool>,
+    cancellation_token: CancellationToken,
 }
 
-/// Specific error to indicate that Rx chain was cancelled by cancellation token.
-#[derive(Debug)]
-pub struct CancelledError;
-
+#[cfg(feature = "std")]
 impl<X> CancellableTx<X> {
-    pub fn new(cancellation_token: Arc<AtomicBool>, tx: X) -> Self {
+    pub fn new(cancellation_token: CancellationToken, tx: X) -> Self {
         CancellableTx {
             tx,
             cancellation_token,
@@ -142,30 +364,32 @@ impl<X> CancellableTx<X> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<X: Tx> Tx for CancellableTx<X> {
     type Item = X::Item;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
-        if self
-            .cancellation_token
-            .load(std::sync::atomic::Ordering::Relaxed)
-        {
-            Err(Box::new(CancelledError))
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if self.cancellation_token.is_cancelled() {
+            Err(FlowError::Cancelled)
         } else {
             self.tx.send(value)
         }
     }
-}
 
-impl fmt::Display for CancelledError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        "Tx was cancelled (via shared AtomicBool)".fmt(f)
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if self.cancellation_token.is_cancelled() {
+            Err(FlowError::Cancelled)
+        } else {
+            self.tx.try_send(value)
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.tx.flush()
     }
-}
 
-impl std::error::Error for CancelledError {
-    fn description(&self) -> &str {
-        "Tx was cancelled (via shared AtomicBool)"
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.tx.close()
     }
 }
 
@@ -180,7 +404,7 @@ pub struct CustomTx<F, T> {
 
 impl<F, T> CustomTx<F, T>
 where
-    F: FnMut(T) -> Result<(), Box<dyn Error>>,
+    F: FnMut(T) -> Result<(), FlowError>,
 {
     pub fn new(handler: F) -> Self {
         CustomTx {
@@ -192,19 +416,356 @@ where
 
 impl<F, T> Tx for CustomTx<F, T>
 where
-    F: FnMut(T) -> Result<(), Box<dyn Error>>,
+    F: FnMut(T) -> Result<(), FlowError>,
 {
     type Item = T;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
         (self.handler)(value)
     }
 }
 
-pub struct ChunksTx<X, T> {
+////////////////////////////////////////////////
+/////////////////// Adapters ///////////////////
+////////////////////////////////////////////////
+
+pub struct WithTx<X, F, U> {
     tx: X,
+    f: F,
+    _marker: PhantomData<fn(U)>,
+}
+
+impl<X, F, U> WithTx<X, F, U> {
+    pub fn new(tx: X, f: F) -> Self {
+        WithTx {
+            tx,
+            f,
+            _marker: Default::default(),
+        }
+    }
+}
+
+impl<X: Tx, F, U> Tx for WithTx<X, F, U>
+where
+    F: FnMut(U) -> X::Item,
+{
+    type Item = U;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.tx.send((self.f)(value))
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.tx.try_send((self.f)(value))
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.tx.close()
+    }
+}
+
+pub struct FilterTx<X, P> {
+    tx: X,
+    predicate: P,
+}
+
+impl<X, P> FilterTx<X, P> {
+    pub fn new(tx: X, predicate: P) -> Self {
+        FilterTx { tx, predicate }
+    }
+}
+
+impl<X: Tx, P> Tx for FilterTx<X, P>
+where
+    P: FnMut(&X::Item) -> bool,
+{
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if (self.predicate)(&value) {
+            self.tx.send(value)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if (self.predicate)(&value) {
+            self.tx.try_send(value)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.tx.close()
+    }
+}
+
+pub struct InspectTx<X, F> {
+    tx: X,
+    f: F,
+}
+
+impl<X, F> InspectTx<X, F> {
+    pub fn new(tx: X, f: F) -> Self {
+        InspectTx { tx, f }
+    }
+}
+
+impl<X: Tx, F> Tx for InspectTx<X, F>
+where
+    F: FnMut(&X::Item),
+{
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        (self.f)(&value);
+        self.tx.send(value)
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        (self.f)(&value);
+        self.tx.try_send(value)
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.tx.close()
+    }
+}
+
+/// Tx counterpart of `DeduplicateRx`. A value which failed to send does not count as the
+/// last one, so sending it again is not suppressed.
+pub struct DedupTx<X: Tx> {
+    tx: X,
+    last: Option<X::Item>,
+}
+
+impl<X: Tx> DedupTx<X> {
+    pub fn new(tx: X) -> Self {
+        DedupTx { tx, last: None }
+    }
+}
+
+impl<X> Tx for DedupTx<X>
+where
+    X: Tx,
+    X::Item: Clone + Eq,
+{
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if self.last.as_ref() == Some(&value) {
+            return Ok(());
+        }
+        self.tx.send(value.clone())?;
+        self.last = Some(value);
+        Ok(())
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if self.last.as_ref() == Some(&value) {
+            return Ok(());
+        }
+        self.tx.try_send(value.clone())?;
+        self.last = Some(value);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.tx.close()
+    }
+}
+
+pub struct MapErrTx<X, F> {
+    tx: X,
+    f: F,
+}
+
+impl<X, F> MapErrTx<X, F> {
+    pub fn new(tx: X, f: F) -> Self {
+        MapErrTx { tx, f }
+    }
+}
+
+impl<X: Tx, F> Tx for MapErrTx<X, F>
+where
+    F: FnMut(FlowError) -> FlowError,
+{
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.tx.send(value).map_err(&mut self.f)
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.tx.try_send(value).map_err(&mut self.f)
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.tx.flush().map_err(&mut self.f)
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        self.tx.close().map_err(&mut self.f)
+    }
+}
+
+////////////////////////////////////////////////
+/////////////////// Fan-out ////////////////////
+////////////////////////////////////////////////
+
+/// Sends every value to both inner Tx. Stops at the first error.
+///
+/// `try_send` is not atomic: if `b` is full, the value has already been sent to `a`.
+pub struct TeeTx<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> TeeTx<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        TeeTx { a, b }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A, B> Tx for TeeTx<A, B>
+where
+    A: Tx,
+    A::Item: Clone,
+    B: Tx<Item = A::Item>,
+{
+    type Item = A::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.a.send(value.clone())?;
+        self.b.send(value)
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        self.a.try_send(value.clone())?;
+        self.b.try_send(value)
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.a.flush()?;
+        self.b.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        let a = self.a.close();
+        let b = self.b.close();
+        a.and(b)
+    }
+}
+
+/// Sends every value to each of any number of inner Tx. Stops at the first error.
+///
+/// `try_send` is not atomic: inner Tx before the first full one have already got the value.
+pub struct BroadcastTx<X> {
+    txs: Vec<X>,
+}
+
+impl<X> BroadcastTx<X> {
+    pub fn new(txs: Vec<X>) -> Self {
+        BroadcastTx { txs }
+    }
+
+    pub fn push(&mut self, tx: X) {
+        self.txs.push(tx);
+    }
+
+    pub fn into_inner(self) -> Vec<X> {
+        self.txs
+    }
+}
+
+impl<X> From<Vec<X>> for BroadcastTx<X> {
+    fn from(txs: Vec<X>) -> Self {
+        Self::new(txs)
+    }
+}
+
+impl<X> Tx for BroadcastTx<X>
+where
+    X: Tx,
+    X::Item: Clone,
+{
+    type Item = X::Item;
+
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if let Some((last, rest)) = self.txs.split_last_mut() {
+            for tx in rest {
+                tx.send(value.clone())?;
+            }
+            last.send(value)?;
+        }
+        Ok(())
+    }
+
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if let Some((last, rest)) = self.txs.split_last_mut() {
+            for tx in rest {
+                tx.try_send(value.clone())?;
+            }
+            last.try_send(value)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        for tx in self.txs.iter_mut() {
+            tx.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Closes every inner Tx, and returns the first error if any.
+    fn close(&mut self) -> Result<(), FlowError> {
+        let mut result = Ok(());
+        for tx in self.txs.iter_mut() {
+            result = result.and(tx.close());
+        }
+        result
+    }
+}
+
+/// What `ChunksTx` should do with incomplete chunk when flushed.
+#[derive(Clone, Copy, Debug)]
+pub enum PartialChunkPolicy<T> {
+    /// Send the chunk as is, i.e. shorter than the chunk size.
+    Emit,
+    /// Fill the chunk up to the chunk size with values produced by the function.
+    Pad(fn() -> T),
+    /// Throw the buffered values away.
+    Discard,
+}
+
+pub struct ChunksTx<X, T> {
+    pub(crate) tx: X,
     chunk_size: usize,
     buffer: Vec<T>,
+    partial: PartialChunkPolicy<T>,
 }
 
 impl<X, T> ChunksTx<X, T> {
@@ -214,14 +775,59 @@ impl<X, T> ChunksTx<X, T> {
             tx,
             chunk_size,
             buffer: Vec::with_capacity(chunk_size),
+            partial: PartialChunkPolicy::Emit,
         }
     }
 
+    /// Set what to do with incomplete chunk on flush. Default is `PartialChunkPolicy::Emit`.
+    pub fn with_partial_policy(mut self, policy: PartialChunkPolicy<T>) -> Self {
+        self.partial = policy;
+        self
+    }
+
     fn swap(&mut self) -> Vec<T> {
         let mut vec = Vec::with_capacity(self.chunk_size);
-        std::mem::swap(&mut vec, &mut self.buffer);
+        core::mem::swap(&mut vec, &mut self.buffer);
         vec
     }
+
+    /// Buffer the value, returning a complete chunk once it is ready to be sent.
+    pub(crate) fn push(&mut self, value: T) -> Option<Vec<T>> {
+        self.buffer.push(value);
+        if self.buffer.len() == self.chunk_size {
+            Some(self.swap())
+        } else {
+            None
+        }
+    }
+
+    /// Take incomplete chunk out of the buffer according to the partial chunk policy.
+    pub(crate) fn take_partial(&mut self) -> Option<Vec<T>> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        match self.partial {
+            PartialChunkPolicy::Emit => Some(self.swap()),
+            PartialChunkPolicy::Pad(pad) => {
+                self.buffer.resize_with(self.chunk_size, pad);
+                Some(self.swap())
+            }
+            PartialChunkPolicy::Discard => {
+                self.buffer.clear();
+                None
+            }
+        }
+    }
+
+    fn send_partial(&mut self) -> Result<(), FlowError>
+    where
+        X: Tx<Item = Vec<T>>,
+    {
+        match self.take_partial() {
+            Some(vec) => self.tx.send(vec),
+            None => Ok(()),
+        }
+    }
 }
 
 impl<X, T> Tx for ChunksTx<X, T>
@@ -230,19 +836,39 @@ where
 {
     type Item = T;
 
-    fn send(&mut self, value: Self::Item) -> Result<(), Box<dyn Error>> {
-        self.buffer.push(value);
-        if self.buffer.len() == self.chunk_size {
-            let vec = self.swap();
+    fn send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if let Some(vec) = self.push(value) {
             self.tx.send(vec)?;
         }
         Ok(())
     }
+
+    /// Only a value which completes a chunk may fail. If the inner Tx is full, the whole chunk
+    /// is discarded along with it.
+    fn try_send(&mut self, value: Self::Item) -> Result<(), FlowError> {
+        if let Some(vec) = self.push(value) {
+            self.tx.try_send(vec)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FlowError> {
+        self.send_partial()?;
+        self.tx.flush()
+    }
+
+    fn close(&mut self) -> Result<(), FlowError> {
+        let sent = self.send_partial();
+        let closed = self.tx.close();
+        sent.and(closed)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::format;
+    use alloc::string::ToString;
 
     #[test]
     fn test_custom() {
@@ -257,4 +883,118 @@ mod test {
 
         assert_eq!(side_effect, 37);
     }
+
+    #[test]
+    fn test_tee_broadcast() {
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        let mut broadcast = BroadcastTx::new(vec![Vec::new(), Vec::new()]);
+        {
+            let mut tx = VecCollectorTx::new(&mut first)
+                .tee(VecCollectorTx::new(&mut second))
+                .tee(&mut broadcast);
+            tx.send_all(vec![1, 2]).unwrap();
+        }
+
+        assert_eq!(first, &[1, 2]);
+        assert_eq!(second, &[1, 2]);
+        assert_eq!(broadcast.into_inner(), &[[1, 2], [1, 2]]);
+    }
+
+    #[test]
+    fn test_with_filter_inspect() {
+        let mut out = Vec::new();
+        let mut seen = 0;
+        {
+            let mut tx = VecCollectorTx::new(&mut out)
+                .inspect(|_| seen += 1)
+                .filter(|i: &i32| i % 2 == 0)
+                .with(|s: &str| s.parse::<i32>().unwrap());
+            tx.send_all(vec!["1", "2", "3", "4"]).unwrap();
+        }
+
+        assert_eq!(out, &[2, 4]);
+        assert_eq!(seen, 2);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut out = Vec::new();
+        VecCollectorTx::new(&mut out)
+            .dedup()
+            .send_all(vec![1, 1, 2, 2, 2, 3, 1])
+            .unwrap();
+        assert_eq!(out, &[1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn test_map_err() {
+        let mut tx = CustomTx::new(|_: i32| Err(FlowError::Disconnected))
+            .map_err(|e| FlowError::other(format!("wrapped: {}", e)));
+        let err = tx.send(1).unwrap_err();
+        assert_eq!(err.to_string(), "wrapped: sending on a closed channel");
+    }
+
+    #[test]
+    fn test_chunks_flush() {
+        let mut out = Vec::new();
+        {
+            let mut tx = VecCollectorTx::new(&mut out).chunks(3);
+            tx.send_all(vec![1, 2, 3, 4]).unwrap();
+            tx.flush().unwrap();
+            // nothing left to flush
+            tx.close().unwrap();
+        }
+        assert_eq!(out, &[vec![1, 2, 3], vec![4]]);
+
+        let mut tx = Vec::new()
+            .chunks(3)
+            .with_partial_policy(PartialChunkPolicy::Pad(|| 0));
+        tx.send_all(vec![1, 2, 3, 4]).unwrap();
+        tx.close().unwrap();
+        assert_eq!(tx.tx, &[[1, 2, 3], [4, 0, 0]]);
+
+        let mut tx = Vec::new()
+            .chunks(3)
+            .with_partial_policy(PartialChunkPolicy::Discard);
+        tx.send_all(vec![1, 2, 3, 4]).unwrap();
+        tx.flush().unwrap();
+        assert_eq!(tx.tx, &[[1, 2, 3]]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_close_after_cancel() {
+        let token = CancellationToken::new();
+        let mut out = Vec::new();
+        {
+            let mut tx = VecCollectorTx::new(&mut out)
+                .chunks(2)
+                .cancel_on(token.clone());
+            tx.send(1).unwrap();
+            token.cancel();
+            assert!(tx.send(2).unwrap_err().is_cancelled());
+            // buffered value still makes it through
+            tx.close().unwrap();
+        }
+        assert_eq!(out, &[vec![1]]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_try_send_does_not_block() {
+        let (sender, mut receiver) = bounded(1, OverflowPolicy::Block);
+        let mut tx = sender.cancel_on(CancellationToken::new());
+        tx.try_send(1).unwrap();
+        assert!(matches!(tx.try_send(2), Err(FlowError::Full)));
+
+        assert_eq!(receiver.try_recv().unwrap(), Some(1));
+
+        // only the value which completes a chunk is sent on
+        let (sender, mut receiver) = bounded(1, OverflowPolicy::Block);
+        let mut tx = sender.chunks(2);
+        tx.send_all(vec![1, 2, 3]).unwrap();
+        assert!(matches!(tx.try_send(4), Err(FlowError::Full)));
+        assert_eq!(receiver.try_recv().unwrap(), Some(vec![1, 2]));
+    }
 }